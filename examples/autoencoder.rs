@@ -3,7 +3,7 @@ use auto_encoder_rust::{
     hashmap,
     model::{Model, Sequential},
     optimizer::get_optimizer_with_defaults,
-    plot::plot_vec,
+    plot::plot_history,
 };
 
 use arrayfire::DType;
@@ -33,7 +33,9 @@ fn main() {
     // model.info();
 
     let source = SinSource::new(input_dims, batch_size, DType::F32, num_train_samples);
-    let loss = model.fit::<SinSource>(&source, epochs, batch_size, None, true);
+    let history = model.fit::<SinSource>(&source, epochs, batch_size, None, true);
+    println!("\nepoch losses: {:?}", history.epoch_losses);
+    history.save_csv("history.csv").unwrap();
 
-    // plot_vec(loss, "Loss vs. Iterations", 512, 512);
+    plot_history(&history, "history.png", 512, 512).unwrap();
 }
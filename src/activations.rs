@@ -3,6 +3,9 @@ use arrayfire::Array;
 
 use crate::{error::HALError, utils};
 
+/// Names of the activations provided by `get_activation`
+pub const ACTIVATIONS: [&str; 2] = ["tanh", "sigmoid"];
+
 /// Returns the tanh activated value
 pub fn tanh(x: &Array<f32>) -> Array<f32> {
     af::tanh(x)
@@ -16,9 +19,23 @@ pub fn tanh_derivative(x: &Array<f32>) -> Array<f32> {
     grad
 }
 
+/// Returns the sigmoid activated value
+pub fn sigmoid(x: &Array<f32>) -> Array<f32> {
+    af::sigmoid(x)
+}
+
+/// Returns the derivative of sigmoid [assumes that sigmoid has already been applied]
+/// sigmoid(x) * (1 - sigmoid(x))
+pub fn sigmoid_derivative(x: &Array<f32>) -> Array<f32> {
+    let one = utils::constant(x.dims(), 1.0f32);
+    let grad = af::mul(x, &af::sub(&one, x, false), false);
+    grad
+}
+
 pub fn get_activation(name: &str, x: &Array<f32>) -> Result<Array<f32>, HALError> {
     match name {
         "tanh" => Ok(tanh(x)),
+        "sigmoid" => Ok(sigmoid(x)),
         _ => Err(HALError::UNKNOWN),
     }
 }
@@ -26,6 +43,7 @@ pub fn get_activation(name: &str, x: &Array<f32>) -> Result<Array<f32>, HALError
 pub fn get_derivative(name: &str, x: &Array<f32>) -> Result<Array<f32>, HALError> {
     match name {
         "tanh" => Ok(tanh_derivative(x)),
+        "sigmoid" => Ok(sigmoid_derivative(x)),
         _ => Err(HALError::UNKNOWN),
     }
 }
@@ -0,0 +1,291 @@
+use af::Array;
+
+use crate::data::{self, DataSouce};
+use crate::error::HALError;
+use crate::model::Sequential;
+use crate::utils;
+
+/// How the anomaly threshold is fitted on (mostly normal) scores
+#[derive(Clone, Copy, Debug)]
+pub enum ThresholdMethod {
+    /// The given percentile (0 - 100) of the scores
+    Percentile(f32),
+    /// mean + k * std of the scores
+    MeanStd(f32),
+    /// The threshold flagging the given fraction of the normal samples
+    FalsePositiveRate(f32),
+}
+
+/// Helper to provide a threshold method from a string
+pub fn get_threshold_method(name: &str, value: f32) -> Result<ThresholdMethod, HALError> {
+    match name {
+        "percentile" => Ok(ThresholdMethod::Percentile(value)),
+        "mean_std" => Ok(ThresholdMethod::MeanStd(value)),
+        "fpr" => Ok(ThresholdMethod::FalsePositiveRate(value)),
+        _ => Err(HALError::UNKNOWN),
+    }
+}
+
+/// Counts of a binary classification, anomalies are the positive class
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConfusionMatrix {
+    pub true_positives: u64,
+    pub false_positives: u64,
+    pub true_negatives: u64,
+    pub false_negatives: u64,
+}
+
+impl ConfusionMatrix {
+    pub fn new(predicted: &[bool], labels: &[bool]) -> ConfusionMatrix {
+        let mut matrix = ConfusionMatrix::default();
+        for (p, l) in predicted.iter().zip(labels.iter()) {
+            match (p, l) {
+                (true, true) => matrix.true_positives += 1,
+                (true, false) => matrix.false_positives += 1,
+                (false, false) => matrix.true_negatives += 1,
+                (false, true) => matrix.false_negatives += 1,
+            }
+        }
+        matrix
+    }
+}
+
+/// Detection quality on labelled data
+#[derive(Clone, Debug)]
+pub struct AnomalyReport {
+    pub threshold: f32,
+    pub roc_auc: f32,
+    pub pr_auc: f32,
+    pub confusion: ConfusionMatrix,
+}
+
+/// Flags samples whose reconstruction score exceeds a fitted threshold
+///
+/// The score of a sample is the per-sample loss of the model (eg: the mean
+/// squared reconstruction error). The threshold is stored on the model so it
+/// is persisted with its checkpoint. Labels are read from the first column of
+/// `Data::labels`, values above 0.5 mark anomalies.
+pub struct AnomalyDetector {
+    pub model: Sequential,
+    pub batch_size: u64,
+}
+
+impl AnomalyDetector {
+    pub fn new(model: Sequential, batch_size: u64) -> AnomalyDetector {
+        AnomalyDetector { model, batch_size }
+    }
+
+    /// Scores of the given inputs, the model reconstructs the inputs themselves
+    pub fn scores(&self, inputs: &Array<f32>) -> Vec<f32> {
+        let num_samples = inputs.dims()[0];
+        let mut scores = Vec::with_capacity(num_samples as usize);
+        let mut start = 0u64;
+        self.model.for_each_batch(
+            num_samples,
+            self.batch_size,
+            |n| {
+                let end = (start + n).min(num_samples);
+                let indices = (start as u32..end as u32).collect::<Vec<u32>>();
+                start = end;
+                let input = data::gather_rows(inputs, &indices);
+                Some(data::Data {
+                    target: input.copy(),
+                    input,
+                    labels: None,
+                })
+            },
+            |batch, predictions| {
+                scores.extend(self.model.sample_losses(predictions, &batch.target));
+            },
+        );
+        scores
+    }
+
+    /// Scores & labels (if the source provides them) of one pass of the test iterator
+    pub fn score_source<T: DataSouce>(&self, source: &T) -> (Vec<f32>, Option<Vec<bool>>) {
+        self.score_pass(source.num_test_samples(), |n| Some(source.get_test_iter(n)))
+    }
+
+    fn score_pass<F>(&self, num_samples: u64, next_batch: F) -> (Vec<f32>, Option<Vec<bool>>)
+    where
+        F: FnMut(u64) -> Option<data::Data>,
+    {
+        let mut scores = Vec::new();
+        let mut labels: Option<Vec<bool>> = Some(Vec::new());
+        self.model.for_each_batch(
+            num_samples,
+            self.batch_size,
+            next_batch,
+            |batch, predictions| {
+                scores.extend(self.model.sample_losses(predictions, &batch.target));
+                labels = match (labels.take(), &batch.labels) {
+                    (Some(mut collected), Some(batch_labels)) => {
+                        let num_samples = batch_labels.dims()[0] as usize;
+                        let values = utils::array_to_vec(batch_labels);
+                        collected.extend(values[..num_samples].iter().map(|v| *v > 0.5));
+                        Some(collected)
+                    }
+                    _ => None,
+                };
+            },
+        );
+        (scores, labels)
+    }
+
+    /// Fits the threshold on the validation data (the test data without validation data)
+    ///
+    /// Known anomalies (labelled samples) and NaN scores are excluded from the fit.
+    pub fn fit_threshold<T: DataSouce>(
+        &mut self,
+        source: &T,
+        method: ThresholdMethod,
+    ) -> Result<f32, HALError> {
+        let (scores, labels) = if source.num_validation_samples() > 0 {
+            self.score_pass(source.num_validation_samples(), |n| {
+                source.get_validation_iter(n)
+            })
+        } else {
+            self.score_source(source)
+        };
+
+        let mut normal = match labels {
+            Some(labels) => scores
+                .iter()
+                .zip(labels.iter())
+                .filter(|(_, anomaly)| !**anomaly)
+                .map(|(score, _)| *score)
+                .collect::<Vec<f32>>(),
+            None => scores,
+        };
+        normal.retain(|score| !score.is_nan());
+        if normal.is_empty() {
+            return Err(HALError::UNKNOWN);
+        }
+        normal.sort_by(|a, b| a.total_cmp(b));
+
+        let threshold = match method {
+            ThresholdMethod::Percentile(p) => data::percentile(&normal, p.max(0.0).min(100.0)),
+            ThresholdMethod::MeanStd(k) => {
+                let n = normal.len() as f32;
+                let mean = normal.iter().sum::<f32>() / n;
+                let var = normal.iter().map(|s| (s - mean) * (s - mean)).sum::<f32>() / n;
+                mean + k * var.sqrt()
+            }
+            ThresholdMethod::FalsePositiveRate(rate) => {
+                data::percentile(&normal, 100.0 * (1.0 - rate.max(0.0).min(1.0)))
+            }
+        };
+
+        self.model.set_anomaly_threshold(Some(threshold));
+        Ok(threshold)
+    }
+
+    /// Flags the scores above the fitted threshold, fails when no threshold was fitted
+    pub fn detect(&self, scores: &[f32]) -> Result<Vec<bool>, HALError> {
+        let threshold = self.model.anomaly_threshold().ok_or(HALError::UNKNOWN)?;
+        Ok(scores.iter().map(|score| *score > threshold).collect())
+    }
+
+    /// Evaluates the detection on the labelled test data of a source
+    pub fn report<T: DataSouce>(&self, source: &T) -> Result<AnomalyReport, HALError> {
+        let threshold = self.model.anomaly_threshold().ok_or(HALError::UNKNOWN)?;
+        let (scores, labels) = self.score_source(source);
+        let labels = labels.ok_or(HALError::UNKNOWN)?;
+        Ok(AnomalyReport {
+            threshold,
+            roc_auc: roc_auc(&scores, &labels),
+            pr_auc: pr_auc(&scores, &labels),
+            confusion: ConfusionMatrix::new(&self.detect(&scores)?, &labels),
+        })
+    }
+}
+
+/// (true positives, false positives) after each distinct score, highest scores first
+///
+/// NaN scores rank above every other score.
+fn ranked_counts(scores: &[f32], labels: &[bool]) -> Vec<(f32, f32)> {
+    let mut order = (0..scores.len()).collect::<Vec<usize>>();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+
+    let mut counts = Vec::new();
+    let (mut tp, mut fp) = (0.0f32, 0.0f32);
+    for (rank, &i) in order.iter().enumerate() {
+        if labels[i] {
+            tp += 1.0;
+        } else {
+            fp += 1.0;
+        }
+        // tied scores share a single operating point
+        let last_of_tie = order
+            .get(rank + 1)
+            .map_or(true, |&next| scores[next] != scores[i]);
+        if last_of_tie {
+            counts.push((tp, fp));
+        }
+    }
+    counts
+}
+
+/// Area under the ROC curve of anomaly scores
+pub fn roc_auc(scores: &[f32], labels: &[bool]) -> f32 {
+    let positives = labels.iter().filter(|l| **l).count() as f32;
+    let negatives = labels.len() as f32 - positives;
+    if positives == 0.0 || negatives == 0.0 {
+        return f32::NAN;
+    }
+
+    let mut area = 0.0f32;
+    let (mut prev_tpr, mut prev_fpr) = (0.0f32, 0.0f32);
+    for (tp, fp) in ranked_counts(scores, labels) {
+        let (tpr, fpr) = (tp / positives, fp / negatives);
+        area += (fpr - prev_fpr) * (tpr + prev_tpr) / 2.0;
+        prev_tpr = tpr;
+        prev_fpr = fpr;
+    }
+    area
+}
+
+/// Area under the precision recall curve (average precision) of anomaly scores
+pub fn pr_auc(scores: &[f32], labels: &[bool]) -> f32 {
+    let positives = labels.iter().filter(|l| **l).count() as f32;
+    if positives == 0.0 {
+        return f32::NAN;
+    }
+
+    let mut area = 0.0f32;
+    let mut prev_recall = 0.0f32;
+    for (tp, fp) in ranked_counts(scores, labels) {
+        let recall = tp / positives;
+        area += (recall - prev_recall) * tp / (tp + fp);
+        prev_recall = recall;
+    }
+    area
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranking_metrics() {
+        let labels = [true, false, true, false];
+        // perfect ranking
+        assert_eq!(roc_auc(&[0.9, 0.1, 0.8, 0.2], &labels), 1.0);
+        assert_eq!(pr_auc(&[0.9, 0.1, 0.8, 0.2], &labels), 1.0);
+        // one anomaly ranked below one normal sample
+        assert_eq!(roc_auc(&[0.9, 0.5, 0.3, 0.2], &labels), 0.75);
+        // ties count as half
+        assert_eq!(roc_auc(&[0.5, 0.5, 0.5, 0.5], &labels), 0.5);
+        // NaN scores rank first instead of panicking
+        assert_eq!(roc_auc(&[f32::NAN, 0.1, 0.8, 0.2], &labels), 1.0);
+    }
+
+    #[test]
+    fn confusion_matrix() {
+        let matrix = ConfusionMatrix::new(&[true, true, false, false], &[true, false, true, false]);
+        assert_eq!(matrix.true_positives, 1);
+        assert_eq!(matrix.false_positives, 1);
+        assert_eq!(matrix.false_negatives, 1);
+        assert_eq!(matrix.true_negatives, 1);
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use arrayfire::Array;
+
+use auto_encoder_rust::{
+    anomaly::AnomalyDetector,
+    callbacks::ModelCheckpoint,
+    config::ExperimentConfig,
+    data::{read_npy, CsvParams, CsvSource, DataSouce, InMemorySource},
+    error::HALError,
+    latent::{self, Latent},
+    model::{Checkpoint, Model, Sequential},
+    optimizer::get_optimizer_with_defaults,
+    utils,
+};
+
+const USAGE: &str = "usage: autoencoder <command> [--option value ..]
+
+commands:
+  train        --config <experiment.toml|json> [--output <dir>]
+  evaluate     --checkpoint <model.json> --input <csv|npy> [--target <csv|npy>] [--metrics mae,rmse]
+  encode       --checkpoint <model.json> --input <csv|npy> --layer <name|index> --output <csv|npy>
+  reconstruct  --checkpoint <model.json> --input <csv|npy> --output <csv|npy>
+  score        --checkpoint <model.json> --input <csv|npy> [--output <csv>]
+
+every command but train accepts --batch-size (defaults to 32)";
+
+/// The subcommand and its `--key value` options
+struct Args {
+    command: String,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Args, String> {
+        let command = args.get(0).ok_or("missing command")?.clone();
+        let mut options = HashMap::new();
+        for pair in args[1..].chunks(2) {
+            let key = pair[0]
+                .strip_prefix("--")
+                .ok_or(format!("unexpected argument {}", pair[0]))?;
+            let value = pair.get(1).ok_or(format!("missing value for --{}", key))?;
+            options.insert(key.to_string(), value.clone());
+        }
+        Ok(Args { command, options })
+    }
+
+    fn required(&self, key: &str) -> Result<&str, String> {
+        self.options
+            .get(key)
+            .map(|v| v.as_str())
+            .ok_or(format!("missing --{}", key))
+    }
+
+    fn batch_size(&self) -> Result<u64, String> {
+        match self.options.get("batch-size") {
+            Some(v) => v
+                .parse::<u64>()
+                .ok()
+                .filter(|size| *size > 0)
+                .ok_or_else(|| "--batch-size must be a positive integer".to_string()),
+            None => Ok(32),
+        }
+    }
+}
+
+fn fail(error: HALError, context: &str) -> String {
+    format!("{}: {:?}", context, error)
+}
+
+/// Reads a [samples, features] table from a .csv/.tsv file or any .npy array
+///
+/// Also returns the column names of a .csv/.tsv header.
+fn read_array(path: &str) -> Result<(Array<f32>, Option<Vec<String>>), String> {
+    let values = if path.ends_with(".npy") {
+        read_npy(path).map(|values| (values, None))
+    } else if path.ends_with(".csv") || path.ends_with(".tsv") {
+        let csv_params = if path.ends_with(".tsv") {
+            CsvParams::tsv()
+        } else {
+            CsvParams::default()
+        };
+        CsvSource::new(path, 1, csv_params).map(|source| (source.input, Some(source.header)))
+    } else {
+        return Err(format!("{} is neither a csv, tsv or npy file", path));
+    };
+    values.map_err(|e| fail(e, path))
+}
+
+/// Writes latent codes as .npy or as a .csv table with z0..zN columns
+fn write_codes(values: &Array<f32>, path: &str) -> Result<(), String> {
+    let result = if path.ends_with(".npy") {
+        utils::write_npy(values, path)
+    } else {
+        Latent {
+            codes: values.copy(),
+            labels: None,
+        }
+        .save_csv(path)
+    };
+    result.map_err(|e| fail(e, path))
+}
+
+/// Restores a checkpoint with the data transforms it was trained with
+///
+/// Every command using it only runs inference, so the optimizer (which is not
+/// part of a checkpoint) is a placeholder and never steps.
+fn load_model(args: &Args) -> Result<(Sequential, Checkpoint), String> {
+    let path = args.required("checkpoint")?;
+    let checkpoint = Checkpoint::load(path).map_err(|e| fail(e, path))?;
+    let optimizer = get_optimizer_with_defaults("sgd").map_err(|e| fail(e, "optimizer"))?;
+    Ok((
+        Sequential::from_checkpoint(&checkpoint, optimizer),
+        checkpoint,
+    ))
+}
+
+/// Reads --input, scaled by the input transform of the checkpoint, with its column names
+fn load_inputs(
+    args: &Args,
+    checkpoint: &Checkpoint,
+) -> Result<(Array<f32>, Option<Vec<String>>), String> {
+    let (inputs, columns) = read_array(args.required("input")?)?;
+    let inputs = match &checkpoint.input_transform {
+        Some(transform) => transform.transform(&inputs),
+        None => inputs,
+    };
+    Ok((inputs, columns))
+}
+
+fn train(args: &Args) -> Result<(), String> {
+    let path = args.required("config")?;
+    let config = ExperimentConfig::load(path).map_err(|e| fail(e, path))?;
+    let output = args.options.get("output").map_or("runs", |v| v.as_str());
+    fs::create_dir_all(output).map_err(|e| e.to_string())?;
+    let output_path = |name: &str| Path::new(output).join(name).to_string_lossy().to_string();
+
+    let mut model = config.build_model().map_err(|e| fail(e, "model"))?;
+    let (source, transforms) = config.build_source().map_err(|e| fail(e, "data"))?;
+    // attached before fit so every checkpoint carries them
+    if let Some((input_transform, target_transform)) = transforms {
+        model.set_transforms(Some(input_transform), Some(target_transform));
+    }
+    let monitor = match source.num_validation_samples() {
+        0 => "loss",
+        _ => "val_loss",
+    };
+    model.add_callback(Box::new(ModelCheckpoint::new(
+        &output_path("checkpoint_{epoch}.json"),
+        monitor,
+        true,
+    )));
+
+    let training = &config.training;
+    let history = model.fit(
+        &source,
+        training.epochs,
+        training.batch_size,
+        None,
+        training.verbose,
+    );
+    if let Some(error) = model.training_error() {
+        return Err(format!("training stopped: {:?}", error));
+    }
+
+    let save = |result: Result<(), HALError>, name: &str| result.map_err(|e| fail(e, name));
+    save(
+        model.checkpoint().save(&output_path("model.json")),
+        "model.json",
+    )?;
+    save(history.save_csv(&output_path("history.csv")), "history.csv")?;
+    save(
+        history.save_json(&output_path("history.json")),
+        "history.json",
+    )?;
+    save(
+        config.with_model(&model).save(&output_path("config.toml")),
+        "config.toml",
+    )?;
+    println!("\nsaved model, history & config to {}", output);
+    Ok(())
+}
+
+fn evaluate(args: &Args) -> Result<(), String> {
+    let (mut model, checkpoint) = load_model(args)?;
+    let (inputs, _) = load_inputs(args, &checkpoint)?;
+    let targets = match args.options.get("target") {
+        Some(path) => {
+            let (targets, _) = read_array(path)?;
+            match &checkpoint.target_transform {
+                Some(transform) => transform.transform(&targets),
+                None => targets,
+            }
+        }
+        None => inputs.copy(),
+    };
+    if let Some(metrics) = args.options.get("metrics") {
+        for metric in metrics.split(',').map(str::trim) {
+            model
+                .add_metric(metric)
+                .map_err(|_| format!("unknown metric {}", metric))?;
+        }
+    }
+
+    let batch_size = args.batch_size()?;
+    let source = InMemorySource::new(inputs, targets, batch_size, false, false, 0);
+    let mut logs = model
+        .evaluate(&source, batch_size)
+        .map_err(|e| fail(e, "evaluate"))?
+        .into_iter()
+        .collect::<Vec<_>>();
+    logs.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, value) in logs {
+        println!("{}: {}", name, value);
+    }
+    Ok(())
+}
+
+fn encode(args: &Args) -> Result<(), String> {
+    let (model, checkpoint) = load_model(args)?;
+    let (inputs, _) = load_inputs(args, &checkpoint)?;
+    let layer = args.required("layer")?;
+    let output = args.required("output")?;
+
+    let batch_size = args.batch_size()?;
+    let source = InMemorySource::new(inputs.copy(), inputs, batch_size, false, false, 0);
+    let codes = latent::extract(&model, &source, layer, batch_size)
+        .map_err(|e| fail(e, layer))?
+        .codes;
+    write_codes(&codes, output)
+}
+
+fn reconstruct(args: &Args) -> Result<(), String> {
+    let (model, checkpoint) = load_model(args)?;
+    let (inputs, columns) = load_inputs(args, &checkpoint)?;
+    let outputs = model
+        .predict(&inputs, args.batch_size()?)
+        .map_err(|e| fail(e, "reconstruct"))?;
+    let outputs = match &checkpoint.target_transform {
+        Some(transform) => transform.inverse_transform(&outputs),
+        None => outputs,
+    };
+
+    let output = args.required("output")?;
+    let result = if output.ends_with(".npy") {
+        utils::write_npy(&outputs, output)
+    } else {
+        // reconstructions live in the input space, so they keep the input column names
+        let num_columns = outputs.elements() / (outputs.dims()[0] as usize).max(1);
+        let header = columns
+            .filter(|columns| columns.len() == num_columns)
+            .unwrap_or_else(|| (0..num_columns).map(|j| format!("x{}", j)).collect());
+        utils::write_csv(&outputs, &header, output)
+    };
+    result.map_err(|e| fail(e, output))
+}
+
+fn score(args: &Args) -> Result<(), String> {
+    let (model, checkpoint) = load_model(args)?;
+    let (inputs, _) = load_inputs(args, &checkpoint)?;
+    let detector = AnomalyDetector::new(model, args.batch_size()?);
+    let scores = detector.scores(&inputs);
+    // flags are only known once a threshold was fitted into the checkpoint
+    let flags = detector.detect(&scores).ok();
+
+    let mut rows = vec![if flags.is_some() {
+        "score,anomaly".to_string()
+    } else {
+        "score".to_string()
+    }];
+    for (i, score) in scores.iter().enumerate() {
+        rows.push(match &flags {
+            Some(flags) => format!("{},{}", score, flags[i] as u8),
+            None => score.to_string(),
+        });
+    }
+    let text = rows.join("\n") + "\n";
+    match args.options.get("output") {
+        Some(path) => fs::write(path, text).map_err(|e| e.to_string()),
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+fn main() {
+    let argv = std::env::args().skip(1).collect::<Vec<String>>();
+    let result = Args::parse(&argv).and_then(|args| match args.command.as_str() {
+        "train" => train(&args),
+        "evaluate" => evaluate(&args),
+        "encode" => encode(&args),
+        "reconstruct" => reconstruct(&args),
+        "score" => score(&args),
+        command => Err(format!("unknown command {}", command)),
+    });
+
+    if let Err(message) = result {
+        eprintln!("error: {}\n\n{}", message, USAGE);
+        process::exit(2);
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::fs::File;
+
+use crate::callbacks::Callback;
+use crate::error::HALError;
+use crate::model::Sequential;
+
+/// Writes the epoch logs as rows of a CSV file
+///
+/// The columns are "epoch" followed by the log keys of the first epoch in
+/// alphabetical order, a key missing at a later epoch is left empty.
+pub struct CsvLogger {
+    pub path: String,
+    writer: Option<csv::Writer<File>>,
+    keys: Vec<String>,
+}
+
+impl CsvLogger {
+    pub fn new(path: &str) -> CsvLogger {
+        CsvLogger {
+            path: path.to_string(),
+            writer: None,
+            keys: Vec::new(),
+        }
+    }
+
+    /// Writes the row of an epoch (and the header on the first epoch)
+    fn write_epoch(&mut self, epoch: u64, logs: &HashMap<String, f32>) -> Result<(), HALError> {
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => return Ok(()),
+        };
+
+        if self.keys.is_empty() {
+            self.keys = logs.keys().cloned().collect();
+            self.keys.sort();
+            let mut header = vec!["epoch".to_string()];
+            header.extend(self.keys.iter().cloned());
+            writer
+                .write_record(&header)
+                .map_err(|_| HALError::IO_ERROR)?;
+        }
+
+        let mut row = vec![epoch.to_string()];
+        row.extend(
+            self.keys
+                .iter()
+                .map(|key| logs.get(key).map_or(String::new(), |v| v.to_string())),
+        );
+        writer.write_record(&row).map_err(|_| HALError::IO_ERROR)?;
+        writer.flush().map_err(|_| HALError::IO_ERROR)
+    }
+}
+
+impl Callback for CsvLogger {
+    fn on_train_begin(&mut self, model: &mut Sequential) {
+        self.keys.clear();
+        match csv::Writer::from_path(&self.path) {
+            Ok(writer) => self.writer = Some(writer),
+            Err(_) => model.fail_training(HALError::IO_ERROR),
+        }
+    }
+
+    fn on_epoch_end(&mut self, epoch: u64, logs: &HashMap<String, f32>, model: &mut Sequential) {
+        if let Err(error) = self.write_epoch(epoch, logs) {
+            self.writer = None;
+            model.fail_training(error);
+        }
+    }
+
+    fn on_train_end(&mut self, _model: &mut Sequential) {
+        self.writer = None;
+    }
+}
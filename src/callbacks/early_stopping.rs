@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use crate::callbacks::Callback;
+use crate::model::{Checkpoint, Sequential};
+
+/// Stops training once the monitored value stopped improving
+///
+/// The value (eg: "val_loss") must decrease by more than `min_delta` within
+/// `patience` epochs. With `restore_best_weights` the parameters of the best
+/// epoch are restored at the end of training.
+pub struct EarlyStopping {
+    pub monitor: String,
+    pub patience: u64,
+    pub min_delta: f32,
+    pub restore_best_weights: bool,
+    best: f32,
+    wait: u64,
+    best_weights: Option<Checkpoint>,
+    stopped_epoch: Option<u64>,
+}
+
+impl EarlyStopping {
+    pub fn new(
+        monitor: &str,
+        patience: u64,
+        min_delta: f32,
+        restore_best_weights: bool,
+    ) -> EarlyStopping {
+        EarlyStopping {
+            monitor: monitor.to_string(),
+            patience,
+            min_delta,
+            restore_best_weights,
+            best: f32::INFINITY,
+            wait: 0,
+            best_weights: None,
+            stopped_epoch: None,
+        }
+    }
+
+    /// Returns the epoch at which training was stopped, if it was
+    pub fn stopped_epoch(&self) -> Option<u64> {
+        self.stopped_epoch
+    }
+}
+
+impl Callback for EarlyStopping {
+    fn on_train_begin(&mut self, _model: &mut Sequential) {
+        self.best = f32::INFINITY;
+        self.wait = 0;
+        self.best_weights = None;
+        self.stopped_epoch = None;
+    }
+
+    fn on_epoch_end(&mut self, epoch: u64, logs: &HashMap<String, f32>, model: &mut Sequential) {
+        let value = match logs.get(&self.monitor) {
+            Some(value) => *value,
+            None => return,
+        };
+
+        if value < self.best - self.min_delta {
+            self.best = value;
+            self.wait = 0;
+            if self.restore_best_weights {
+                self.best_weights = Some(model.checkpoint());
+            }
+        } else {
+            self.wait += 1;
+            if self.wait >= self.patience {
+                self.stopped_epoch = Some(epoch);
+                model.stop_training();
+            }
+        }
+    }
+
+    fn on_train_end(&mut self, model: &mut Sequential) {
+        if let Some(best_weights) = &self.best_weights {
+            model.restore(best_weights);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::dense_model;
+
+    #[test]
+    fn stops_after_patience() {
+        let mut model = dense_model(&[(2, 2)], "tanh");
+
+        let mut early_stopping = EarlyStopping::new("val_loss", 2, 0.0, false);
+        early_stopping.on_train_begin(&mut model);
+        for (epoch, value) in [1.0, 0.5, 0.6, 0.7].iter().enumerate() {
+            let logs = [("val_loss".to_string(), *value)].iter().cloned().collect();
+            early_stopping.on_epoch_end(epoch as u64, &logs, &mut model);
+        }
+        assert_eq!(early_stopping.stopped_epoch(), Some(3));
+        assert!(model.is_stopping());
+    }
+}
@@ -0,0 +1,50 @@
+use crate::callbacks::Callback;
+use crate::model::Sequential;
+
+/// Sets the optimizer learning rate at the beginning of every epoch
+///
+/// The schedule maps (epoch, initial learning rate) to the learning rate of
+/// that epoch, the initial rate is read from the optimizer when training begins.
+pub struct LearningRateScheduler {
+    schedule: Box<dyn Fn(u64, f32) -> f32>,
+    initial: f32,
+}
+
+impl LearningRateScheduler {
+    pub fn new(schedule: Box<dyn Fn(u64, f32) -> f32>) -> LearningRateScheduler {
+        LearningRateScheduler {
+            schedule,
+            initial: 0.0,
+        }
+    }
+
+    /// lr = initial * factor^(epoch / every)
+    pub fn step_decay(factor: f32, every: u64) -> LearningRateScheduler {
+        LearningRateScheduler::new(Box::new(move |epoch, initial| {
+            initial * factor.powi((epoch / every.max(1)) as i32)
+        }))
+    }
+
+    /// lr = initial * gamma^epoch
+    pub fn exponential_decay(gamma: f32) -> LearningRateScheduler {
+        LearningRateScheduler::new(Box::new(move |epoch, initial| {
+            initial * gamma.powi(epoch as i32)
+        }))
+    }
+}
+
+impl Callback for LearningRateScheduler {
+    fn on_train_begin(&mut self, model: &mut Sequential) {
+        self.initial = model.optimizer().learning_rate();
+    }
+
+    fn on_epoch_begin(&mut self, epoch: u64, model: &mut Sequential) {
+        let learning_rate = (self.schedule)(epoch, self.initial);
+        model.optimizer_mut().set_learning_rate(learning_rate);
+    }
+
+    fn on_train_end(&mut self, model: &mut Sequential) {
+        // leave the optimizer as it was before training
+        model.optimizer_mut().set_learning_rate(self.initial);
+    }
+}
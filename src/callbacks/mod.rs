@@ -0,0 +1,39 @@
+mod csv_logger;
+mod early_stopping;
+mod lr_scheduler;
+mod model_checkpoint;
+mod tensorboard;
+mod terminate_on_nan;
+use std::collections::HashMap;
+
+pub use self::csv_logger::CsvLogger;
+pub use self::early_stopping::EarlyStopping;
+pub use self::lr_scheduler::LearningRateScheduler;
+pub use self::model_checkpoint::ModelCheckpoint;
+pub use self::tensorboard::{EventWriter, TensorBoard};
+pub use self::terminate_on_nan::TerminateOnNaN;
+use crate::model::Sequential;
+
+/// Hooks called by `Sequential::fit` during training
+///
+/// Every hook receives the model, so a callback can inspect or modify the
+/// parameters, change the optimizer (eg: its learning rate) or request the
+/// end of training with `Sequential::stop_training`. Hooks that fail (eg: on
+/// IO) report it with `Sequential::fail_training`, which also stops training
+/// and leaves the error in `Sequential::training_error`. Batch logs hold the
+/// "loss" of the minibatch (and its "activity_loss" when activity penalties
+/// are used), epoch logs hold their means, the "val_loss" (when the source
+/// has validation data) and the "lr".
+/// `on_backward_end` runs before the optimizer update, while the gradients
+/// of the minibatch are still held by the parameters.
+pub trait Callback {
+    fn on_train_begin(&mut self, _model: &mut Sequential) {}
+    fn on_train_end(&mut self, _model: &mut Sequential) {}
+    fn on_epoch_begin(&mut self, _epoch: u64, _model: &mut Sequential) {}
+    fn on_epoch_end(&mut self, _epoch: u64, _logs: &HashMap<String, f32>, _model: &mut Sequential) {
+    }
+    fn on_batch_begin(&mut self, _batch: u64, _model: &mut Sequential) {}
+    fn on_backward_end(&mut self, _batch: u64, _model: &mut Sequential) {}
+    fn on_batch_end(&mut self, _batch: u64, _logs: &HashMap<String, f32>, _model: &mut Sequential) {
+    }
+}
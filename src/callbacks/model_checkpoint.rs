@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use crate::callbacks::Callback;
+use crate::model::Sequential;
+
+/// Saves a model checkpoint at the end of epochs
+///
+/// With `save_best_only` a checkpoint is only written when the monitored
+/// value improved. An "{epoch}" placeholder in `path` is replaced by the
+/// epoch number, otherwise every save overwrites the same file.
+pub struct ModelCheckpoint {
+    pub path: String,
+    pub monitor: String,
+    pub save_best_only: bool,
+    best: f32,
+}
+
+impl ModelCheckpoint {
+    pub fn new(path: &str, monitor: &str, save_best_only: bool) -> ModelCheckpoint {
+        ModelCheckpoint {
+            path: path.to_string(),
+            monitor: monitor.to_string(),
+            save_best_only,
+            best: f32::INFINITY,
+        }
+    }
+}
+
+impl Callback for ModelCheckpoint {
+    fn on_train_begin(&mut self, _model: &mut Sequential) {
+        self.best = f32::INFINITY;
+    }
+
+    fn on_epoch_end(&mut self, epoch: u64, logs: &HashMap<String, f32>, model: &mut Sequential) {
+        if self.save_best_only {
+            match logs.get(&self.monitor) {
+                Some(value) if *value < self.best => self.best = *value,
+                _ => return,
+            }
+        }
+
+        let path = self.path.replace("{epoch}", &epoch.to_string());
+        if let Err(error) = model.checkpoint().save(&path) {
+            model.fail_training(error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::HALError;
+    use crate::test_utils::dense_model;
+
+    #[test]
+    fn failed_save_stops_training() {
+        let mut model = dense_model(&[(2, 2)], "tanh");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing").join("model.json");
+        let mut checkpoint = ModelCheckpoint::new(path.to_str().unwrap(), "loss", false);
+        checkpoint.on_epoch_end(0, &HashMap::new(), &mut model);
+        assert!(model.is_stopping());
+        assert!(matches!(model.training_error(), Some(HALError::IO_ERROR)));
+    }
+}
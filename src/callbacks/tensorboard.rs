@@ -0,0 +1,477 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use af::Array;
+
+use crate::callbacks::Callback;
+use crate::error::HALError;
+use crate::model::{Model, Sequential};
+use crate::params::ParamManager;
+use crate::plot;
+use crate::utils;
+
+const NUM_BUCKETS: usize = 30;
+
+/// Number of event files opened by this process, keeps their names unique
+static NUM_WRITERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Appends TensorBoard events (scalars, histograms & images) to an event file
+///
+/// Events are length delimited `Event` protobufs framed with masked CRC32C
+/// checksums, the file is named `events.out.tfevents.{time}.{host}.{pid}.{n}`
+/// so TensorBoard picks it up from the log directory, the process id & the
+/// per process counter `n` keep writers opened within a second apart.
+pub struct EventWriter {
+    pub path: String,
+    writer: BufWriter<File>,
+}
+
+impl EventWriter {
+    pub fn new(log_dir: &str) -> Result<EventWriter, HALError> {
+        fs::create_dir_all(log_dir).map_err(|_| HALError::IO_ERROR)?;
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "localhost".to_string());
+        let file_name = format!(
+            "events.out.tfevents.{}.{}.{}.{}",
+            wall_time() as u64,
+            host,
+            std::process::id(),
+            NUM_WRITERS.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = Path::new(log_dir).join(file_name);
+        let file = File::create(&path).map_err(|_| HALError::IO_ERROR)?;
+
+        let mut event_writer = EventWriter {
+            path: path.to_string_lossy().to_string(),
+            writer: BufWriter::new(file),
+        };
+        let mut event = Proto::default();
+        event.double(1, wall_time());
+        event.bytes(3, b"brain.Event:2");
+        event_writer.write_record(&event.buffer)?;
+        event_writer.flush()?;
+        Ok(event_writer)
+    }
+
+    pub fn add_scalar(&mut self, tag: &str, value: f32, step: u64) -> Result<(), HALError> {
+        let mut summary_value = Proto::default();
+        summary_value.bytes(1, tag.as_bytes());
+        summary_value.float(2, value);
+        self.write_summary(&summary_value, step)
+    }
+
+    /// Histogram of the values over equal width buckets between their min & max
+    pub fn add_histogram(&mut self, tag: &str, values: &[f32], step: u64) -> Result<(), HALError> {
+        let values = values
+            .iter()
+            .filter(|v| v.is_finite())
+            .map(|v| *v as f64)
+            .collect::<Vec<f64>>();
+        if values.is_empty() {
+            return Ok(());
+        }
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let width = (max - min).max(1e-12) / NUM_BUCKETS as f64;
+
+        let mut counts = vec![0f64; NUM_BUCKETS];
+        for v in &values {
+            let bucket = ((v - min) / width) as usize;
+            counts[bucket.min(NUM_BUCKETS - 1)] += 1.0;
+        }
+        let limits = (1..=NUM_BUCKETS)
+            .map(|i| min + i as f64 * width)
+            .collect::<Vec<f64>>();
+
+        let mut histogram = Proto::default();
+        histogram.double(1, min);
+        histogram.double(2, max);
+        histogram.double(3, values.len() as f64);
+        histogram.double(4, values.iter().sum());
+        histogram.double(5, values.iter().map(|v| v * v).sum());
+        histogram.packed_doubles(6, &limits);
+        histogram.packed_doubles(7, &counts);
+
+        let mut summary_value = Proto::default();
+        summary_value.bytes(1, tag.as_bytes());
+        summary_value.bytes(5, &histogram.buffer);
+        self.write_summary(&summary_value, step)
+    }
+
+    /// Adds an RGB image (row major, 3 bytes per pixel) encoded as PNG
+    pub fn add_image(
+        &mut self,
+        tag: &str,
+        rgb: &[u8],
+        width: u32,
+        height: u32,
+        step: u64,
+    ) -> Result<(), HALError> {
+        let mut image = Proto::default();
+        image.varint(1, height as u64);
+        image.varint(2, width as u64);
+        image.varint(3, 3);
+        image.bytes(4, &plot::encode_png(rgb, width, height)?);
+
+        let mut summary_value = Proto::default();
+        summary_value.bytes(1, tag.as_bytes());
+        summary_value.bytes(4, &image.buffer);
+        self.write_summary(&summary_value, step)
+    }
+
+    pub fn flush(&mut self) -> Result<(), HALError> {
+        self.writer.flush().map_err(|_| HALError::IO_ERROR)
+    }
+
+    fn write_summary(&mut self, summary_value: &Proto, step: u64) -> Result<(), HALError> {
+        let mut summary = Proto::default();
+        summary.bytes(1, &summary_value.buffer);
+        let mut event = Proto::default();
+        event.double(1, wall_time());
+        event.varint(2, step);
+        event.bytes(5, &summary.buffer);
+        self.write_record(&event.buffer)
+    }
+
+    fn write_record(&mut self, data: &[u8]) -> Result<(), HALError> {
+        let length = (data.len() as u64).to_le_bytes();
+        let mut record = Vec::with_capacity(data.len() + 16);
+        record.extend_from_slice(&length);
+        record.extend_from_slice(&masked_crc32c(&length).to_le_bytes());
+        record.extend_from_slice(data);
+        record.extend_from_slice(&masked_crc32c(data).to_le_bytes());
+        self.writer
+            .write_all(&record)
+            .map_err(|_| HALError::IO_ERROR)
+    }
+}
+
+fn wall_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64())
+}
+
+/// Minimal protobuf encoder for the few messages of an event file
+#[derive(Default)]
+struct Proto {
+    buffer: Vec<u8>,
+}
+
+impl Proto {
+    fn key(&mut self, field: u32, wire_type: u8) {
+        self.raw_varint(((field as u64) << 3) | wire_type as u64);
+    }
+
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buffer.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.buffer.push(value as u8);
+    }
+
+    fn varint(&mut self, field: u32, value: u64) {
+        self.key(field, 0);
+        self.raw_varint(value);
+    }
+
+    fn double(&mut self, field: u32, value: f64) {
+        self.key(field, 1);
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn float(&mut self, field: u32, value: f32) {
+        self.key(field, 5);
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, field: u32, value: &[u8]) {
+        self.key(field, 2);
+        self.raw_varint(value.len() as u64);
+        self.buffer.extend_from_slice(value);
+    }
+
+    fn packed_doubles(&mut self, field: u32, values: &[f64]) {
+        let packed = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect::<Vec<u8>>();
+        self.bytes(field, &packed);
+    }
+}
+
+/// CRC32C (Castagnoli) of the data
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn masked_crc32c(data: &[u8]) -> u32 {
+    let crc = crc32c(data);
+    ((crc >> 15) | (crc << 17)).wrapping_add(0xa282_ead8)
+}
+
+/// Logs training to TensorBoard event files in `log_dir`
+///
+/// Batch logs are written as "batch/{key}" scalars at the global iteration and
+/// epoch logs (loss, metrics, lr, val_*) as "epoch/{key}" scalars. Every
+/// `histogram_freq` epochs (0 disables them) histograms of the weights and of
+/// the gradients of the first minibatch are added, gradients are summed over
+/// that minibatch. Images added with `with_images` are reconstructed at the
+/// end of every epoch.
+pub struct TensorBoard {
+    pub log_dir: String,
+    pub histogram_freq: u64,
+    writer: Option<EventWriter>,
+    images: Option<(Array<f32>, u32, u32)>,
+    epoch: u64,
+    step: u64,
+}
+
+impl TensorBoard {
+    pub fn new(log_dir: &str, histogram_freq: u64) -> TensorBoard {
+        TensorBoard {
+            log_dir: log_dir.to_string(),
+            histogram_freq,
+            writer: None,
+            images: None,
+            epoch: 0,
+            step: 0,
+        }
+    }
+
+    /// Logs the reconstructions of these [samples, height * width] inputs next to them
+    ///
+    /// Fails when the inputs do not hold `height * width` features.
+    pub fn with_images(
+        mut self,
+        inputs: Array<f32>,
+        height: u32,
+        width: u32,
+    ) -> Result<TensorBoard, HALError> {
+        if inputs.dims()[1] != height as u64 * width as u64 {
+            return Err(HALError::UNKNOWN);
+        }
+        self.images = Some((inputs, height, width));
+        Ok(self)
+    }
+
+    /// Stops training on a failed write, nothing more is logged afterwards
+    fn report(&mut self, result: Result<(), HALError>, model: &mut Sequential) {
+        if let Err(error) = result {
+            self.writer = None;
+            model.fail_training(error);
+        }
+    }
+
+    fn log_scalars(
+        &mut self,
+        prefix: &str,
+        logs: &HashMap<String, f32>,
+        step: u64,
+    ) -> Result<(), HALError> {
+        if let Some(writer) = self.writer.as_mut() {
+            for (key, value) in logs {
+                writer.add_scalar(&format!("{}/{}", prefix, key), *value, step)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn log_histograms(
+        &mut self,
+        model: &Sequential,
+        prefix: &str,
+        gradients: bool,
+    ) -> Result<(), HALError> {
+        if let Some(writer) = self.writer.as_mut() {
+            for (name, values) in named_arrays(model.param_manager(), gradients) {
+                writer.add_histogram(&format!("{}/{}", prefix, name), &values, self.epoch)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn histograms_due(&self) -> bool {
+        self.histogram_freq > 0 && self.epoch % self.histogram_freq == 0
+    }
+
+    fn log_reconstructions(&mut self, model: &Sequential) -> Result<(), HALError> {
+        let (inputs, height, width) = match &self.images {
+            Some(images) => images,
+            None => return Ok(()),
+        };
+        let (height, width) = (*height as usize, *width as usize);
+        let num_samples = inputs.dims()[0] as usize;
+        let outputs = model.predict(inputs, num_samples as u64)?;
+        let (x, y) = (utils::array_to_vec(inputs), utils::array_to_vec(&outputs));
+
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => return Ok(()),
+        };
+        for i in 0..num_samples {
+            // input on the left, reconstruction on the right
+            let mut rgb = Vec::with_capacity(height * width * 2 * 3);
+            for r in 0..height {
+                for values in [&x, &y].iter() {
+                    for c in 0..width {
+                        // samples are rows, pixels are stored row major in the features
+                        let v = values[i + num_samples * (r * width + c)];
+                        let gray = (v.max(0.0).min(1.0) * 255.0).round() as u8;
+                        rgb.extend_from_slice(&[gray, gray, gray]);
+                    }
+                }
+            }
+            writer.add_image(
+                &format!("reconstruction/{}", i),
+                &rgb,
+                2 * width as u32,
+                height as u32,
+                self.epoch,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Names & values of the weights (or their gradients) of every layer
+fn named_arrays(param_manager: &ParamManager, gradients: bool) -> Vec<(String, Vec<f32>)> {
+    let mut arrays = Vec::new();
+    for layer in 0..param_manager.num_layers() {
+        let num_weights = param_manager.num_weights(layer);
+        let values = if gradients {
+            param_manager.get_deltas(layer)
+        } else {
+            let mut values = param_manager.get_weights(layer);
+            values.extend(param_manager.get_biases(layer));
+            values
+        };
+        for (i, array) in values.iter().enumerate() {
+            let name = if i < num_weights {
+                format!("layer{}/weight{}", layer, i)
+            } else {
+                format!("layer{}/bias{}", layer, i - num_weights)
+            };
+            arrays.push((name, utils::array_to_vec(array)));
+        }
+    }
+    arrays
+}
+
+impl Callback for TensorBoard {
+    fn on_train_begin(&mut self, model: &mut Sequential) {
+        self.step = 0;
+        match EventWriter::new(&self.log_dir) {
+            Ok(writer) => self.writer = Some(writer),
+            Err(error) => self.report(Err(error), model),
+        }
+    }
+
+    fn on_epoch_begin(&mut self, epoch: u64, _model: &mut Sequential) {
+        self.epoch = epoch;
+    }
+
+    fn on_backward_end(&mut self, batch: u64, model: &mut Sequential) {
+        if batch != 0 || !self.histograms_due() {
+            return;
+        }
+        let result = self.log_histograms(model, "gradients", true);
+        self.report(result, model);
+    }
+
+    fn on_batch_end(&mut self, _batch: u64, logs: &HashMap<String, f32>, model: &mut Sequential) {
+        let result = self.log_scalars("batch", logs, self.step);
+        self.report(result, model);
+        self.step += 1;
+    }
+
+    fn on_epoch_end(&mut self, epoch: u64, logs: &HashMap<String, f32>, model: &mut Sequential) {
+        let mut result = self.log_scalars("epoch", logs, epoch);
+        if result.is_ok() && self.histograms_due() {
+            result = self.log_histograms(model, "weights", false);
+        }
+        if result.is_ok() {
+            result = self.log_reconstructions(model);
+        }
+        if let (true, Some(writer)) = (result.is_ok(), self.writer.as_mut()) {
+            result = writer.flush();
+        }
+        self.report(result, model);
+    }
+
+    fn on_train_end(&mut self, model: &mut Sequential) {
+        if let Some(mut writer) = self.writer.take() {
+            let result = writer.flush();
+            self.report(result, model);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32c_check_value() {
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+    }
+
+    #[test]
+    fn framed_records() {
+        let log_dir = tempfile::tempdir().unwrap();
+        let mut writer = EventWriter::new(log_dir.path().to_str().unwrap()).unwrap();
+        writer.add_scalar("loss", 0.5, 1).unwrap();
+        writer
+            .add_histogram("weights", &[0.0, 1.0, 2.0], 1)
+            .unwrap();
+        writer.flush().unwrap();
+
+        // every record is [length, crc(length), data, crc(data)]
+        let bytes = fs::read(&writer.path).unwrap();
+        let (mut offset, mut num_records) = (0, 0);
+        while offset < bytes.len() {
+            let length = &bytes[offset..offset + 8];
+            let crc = u32::from_le_bytes([
+                bytes[offset + 8],
+                bytes[offset + 9],
+                bytes[offset + 10],
+                bytes[offset + 11],
+            ]);
+            assert_eq!(crc, masked_crc32c(length));
+            let mut size = [0u8; 8];
+            size.copy_from_slice(length);
+            offset += 16 + u64::from_le_bytes(size) as usize;
+            num_records += 1;
+        }
+        assert_eq!(offset, bytes.len());
+        assert_eq!(num_records, 3);
+
+        // a second writer in the same second gets its own file
+        let other = EventWriter::new(log_dir.path().to_str().unwrap()).unwrap();
+        assert_ne!(other.path, writer.path);
+    }
+
+    #[test]
+    fn image_shape_is_checked() {
+        let inputs = af::constant(0.5f32, af::Dim4::new(&[2, 6, 1, 1]));
+        let tensorboard = TensorBoard::new("logs", 0);
+        assert!(tensorboard.with_images(inputs.copy(), 4, 2).is_err());
+        let tensorboard = TensorBoard::new("logs", 0);
+        assert!(tensorboard.with_images(inputs, 3, 2).is_ok());
+    }
+}
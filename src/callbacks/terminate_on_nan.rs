@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+
+use crate::callbacks::Callback;
+use crate::model::Sequential;
+
+/// Stops training as soon as a minibatch loss is NaN or infinite
+///
+/// With `verbose` the offending batch is printed.
+#[derive(Default)]
+pub struct TerminateOnNaN {
+    pub verbose: bool,
+}
+
+impl TerminateOnNaN {
+    pub fn new(verbose: bool) -> TerminateOnNaN {
+        TerminateOnNaN { verbose }
+    }
+}
+
+impl Callback for TerminateOnNaN {
+    fn on_batch_end(&mut self, batch: u64, logs: &HashMap<String, f32>, model: &mut Sequential) {
+        if let Some(loss) = logs.get("loss") {
+            if !loss.is_finite() {
+                if self.verbose {
+                    println!(
+                        "\nbatch {}: invalid loss {}, terminating training",
+                        batch, loss
+                    );
+                }
+                model.stop_training();
+            }
+        }
+    }
+}
@@ -0,0 +1,510 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use af::DType;
+use serde::{Deserialize, Serialize};
+
+use crate::data::{
+    self, CsvParams, CsvSource, DataSouce, IdxSource, NpySource, SinSource, SplitStrategy,
+    Transform, TransformedSource,
+};
+use crate::error::HALError;
+use crate::initializations;
+use crate::model::{LayerConfig, Model, ParamValue, Sequential};
+use crate::optimizer::{self, Optimizer};
+
+/// A layer table: its type and the params given to `Model::add`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayerSpec {
+    pub layer: String,
+    #[serde(flatten)]
+    pub params: BTreeMap<String, ParamValue>,
+}
+
+impl LayerSpec {
+    pub fn from_layer_config(config: &LayerConfig) -> LayerSpec {
+        LayerSpec {
+            layer: config.layer.clone(),
+            params: config.params.clone(),
+        }
+    }
+
+    pub fn to_layer_config(&self) -> LayerConfig {
+        LayerConfig {
+            layer: self.layer.clone(),
+            params: self.params.clone(),
+        }
+    }
+}
+
+fn default_loss() -> String {
+    "mse".to_string()
+}
+
+/// The architecture of a `Sequential` model
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModelConfig {
+    #[serde(default = "default_loss")]
+    pub loss: String,
+    #[serde(default)]
+    pub metrics: Vec<String>,
+    pub layers: Vec<LayerSpec>,
+}
+
+impl ModelConfig {
+    /// Exports the architecture of a model (its layers were added through `add`)
+    pub fn from_model(model: &Sequential) -> ModelConfig {
+        ModelConfig {
+            loss: model.loss().to_string(),
+            metrics: model.metrics().to_vec(),
+            layers: model
+                .layer_configs()
+                .iter()
+                .map(LayerSpec::from_layer_config)
+                .collect(),
+        }
+    }
+}
+
+fn default_optimizer() -> String {
+    "sgd".to_string()
+}
+
+/// An optimizer name and its params (see `optimizer::get_optimizer`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OptimizerConfig {
+    #[serde(default = "default_optimizer")]
+    pub name: String,
+    #[serde(flatten)]
+    pub params: BTreeMap<String, ParamValue>,
+}
+
+impl Default for OptimizerConfig {
+    fn default() -> Self {
+        OptimizerConfig {
+            name: default_optimizer(),
+            params: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Where the samples come from, tagged by `source`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "lowercase")]
+pub enum SourceConfig {
+    Csv {
+        path: String,
+        /// A single character, "\t" for TSV (defaults to ",")
+        #[serde(default)]
+        delimiter: Option<String>,
+        #[serde(default = "default_true")]
+        has_header: bool,
+        #[serde(default)]
+        input_columns: Option<Vec<String>>,
+        #[serde(default)]
+        target_columns: Option<Vec<String>>,
+    },
+    Npy {
+        path: String,
+        #[serde(default)]
+        target_path: Option<String>,
+    },
+    Npz {
+        path: String,
+        input_name: String,
+        #[serde(default)]
+        target_name: Option<String>,
+    },
+    Idx {
+        path: String,
+        #[serde(default = "default_true")]
+        flatten: bool,
+    },
+    Sin {
+        input_size: u64,
+        num_samples: u64,
+    },
+}
+
+/// A data source with an optional random validation / test split
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DataConfig {
+    #[serde(flatten)]
+    pub source: SourceConfig,
+    #[serde(default)]
+    pub validation_split: f32,
+    #[serde(default)]
+    pub test_split: f32,
+    /// Scaling fitted on the training samples (see `data::get_transform_kind`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<String>,
+}
+
+/// A source built from a `DataConfig` and its fitted (input, target) transforms
+pub type BuiltSource = (Box<dyn DataSouce>, Option<(Transform, Transform)>);
+
+impl DataConfig {
+    /// Loads the source, splitting it when a validation or test fraction is given
+    ///
+    /// With a `transform` the returned source is scaled and the transforms
+    /// fitted on its training samples are returned along with it.
+    pub fn build(&self, batch_size: u64, seed: u64) -> Result<BuiltSource, HALError> {
+        let source: Box<dyn DataSouce> = match &self.source {
+            SourceConfig::Csv {
+                path,
+                delimiter,
+                has_header,
+                input_columns,
+                target_columns,
+            } => {
+                let delimiter = match delimiter.as_deref() {
+                    None => b',',
+                    Some("\\t") | Some("\t") => b'\t',
+                    Some(d) if d.len() == 1 => d.as_bytes()[0],
+                    Some(_) => return Err(HALError::PARSE_ERROR),
+                };
+                let csv_params = CsvParams {
+                    delimiter,
+                    has_header: *has_header,
+                    input_columns: input_columns.clone(),
+                    target_columns: target_columns.clone(),
+                };
+                Box::new(CsvSource::new(path, batch_size, csv_params)?)
+            }
+            SourceConfig::Npy { path, target_path } => {
+                Box::new(NpySource::new(path, target_path.as_deref(), batch_size)?)
+            }
+            SourceConfig::Npz {
+                path,
+                input_name,
+                target_name,
+            } => Box::new(NpySource::from_npz(
+                path,
+                input_name,
+                target_name.as_deref(),
+                batch_size,
+            )?),
+            SourceConfig::Idx { path, flatten } => {
+                Box::new(IdxSource::new(path, batch_size, *flatten)?)
+            }
+            SourceConfig::Sin {
+                input_size,
+                num_samples,
+            } => Box::new(SinSource::new(
+                *input_size,
+                batch_size,
+                DType::F32,
+                *num_samples,
+            )),
+        };
+
+        let source: Box<dyn DataSouce> = if self.validation_split <= 0.0 && self.test_split <= 0.0 {
+            source
+        } else {
+            let strategy = SplitStrategy::Ratio {
+                train: 1.0 - self.validation_split - self.test_split,
+                validation: self.validation_split,
+            };
+            Box::new(data::split(&source, strategy, None, batch_size, seed)?)
+        };
+
+        let kind = match &self.transform {
+            Some(name) => data::get_transform_kind(name).map_err(|_| HALError::PARSE_ERROR)?,
+            None => return Ok((source, None)),
+        };
+        // a split source serves its train partition to `fit`
+        let source = TransformedSource::fit(source, kind)?;
+        let transforms = (
+            source.input_transform.clone(),
+            source.target_transform.clone(),
+        );
+        Ok((Box::new(source), Some(transforms)))
+    }
+}
+
+fn default_epochs() -> u64 {
+    10
+}
+
+fn default_batch_size() -> u64 {
+    32
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TrainingConfig {
+    #[serde(default = "default_epochs")]
+    pub epochs: u64,
+    #[serde(default = "default_batch_size")]
+    pub batch_size: u64,
+    /// Seeds the weight initializations & the data split (random when missing)
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub verbose: bool,
+}
+
+impl Default for TrainingConfig {
+    fn default() -> Self {
+        TrainingConfig {
+            epochs: default_epochs(),
+            batch_size: default_batch_size(),
+            seed: None,
+            verbose: false,
+        }
+    }
+}
+
+/// A full experiment read from (or written to) a TOML or JSON file
+///
+/// ```toml
+/// [model]
+/// loss = "mse"
+///
+/// [[model.layers]]
+/// layer = "dense"
+/// activation = "tanh"
+/// input_size = 8
+/// output_size = 2
+/// w_init = "uniform"
+/// b_init = "zeros"
+///
+/// [optimizer]
+/// name = "sgd"
+/// learning_rate = 0.01
+///
+/// [data]
+/// source = "csv"
+/// path = "train.csv"
+/// validation_split = 0.1
+/// transform = "minmax"
+///
+/// [training]
+/// epochs = 20
+/// batch_size = 32
+/// seed = 42
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExperimentConfig {
+    pub model: ModelConfig,
+    #[serde(default)]
+    pub optimizer: OptimizerConfig,
+    #[serde(default)]
+    pub data: Option<DataConfig>,
+    #[serde(default)]
+    pub training: TrainingConfig,
+}
+
+impl ExperimentConfig {
+    pub fn from_toml(text: &str) -> Result<ExperimentConfig, HALError> {
+        toml::from_str(text).map_err(|_| HALError::PARSE_ERROR)
+    }
+
+    pub fn from_json(text: &str) -> Result<ExperimentConfig, HALError> {
+        serde_json::from_str(text).map_err(|_| HALError::PARSE_ERROR)
+    }
+
+    pub fn to_toml(&self) -> Result<String, HALError> {
+        toml::to_string_pretty(self).map_err(|_| HALError::UNKNOWN)
+    }
+
+    pub fn to_json(&self) -> Result<String, HALError> {
+        serde_json::to_string_pretty(self).map_err(|_| HALError::UNKNOWN)
+    }
+
+    /// Reads a .toml or .json config file
+    pub fn load(path: &str) -> Result<ExperimentConfig, HALError> {
+        let text = fs::read_to_string(path).map_err(|_| HALError::IO_ERROR)?;
+        if path.ends_with(".toml") {
+            ExperimentConfig::from_toml(&text)
+        } else if path.ends_with(".json") {
+            ExperimentConfig::from_json(&text)
+        } else {
+            Err(HALError::PARSE_ERROR)
+        }
+    }
+
+    /// Writes the config as .toml or .json (chosen by the extension of `path`)
+    pub fn save(&self, path: &str) -> Result<(), HALError> {
+        let text = if path.ends_with(".toml") {
+            self.to_toml()?
+        } else if path.ends_with(".json") {
+            self.to_json()?
+        } else {
+            return Err(HALError::UNKNOWN);
+        };
+        fs::write(path, text).map_err(|_| HALError::IO_ERROR)
+    }
+
+    /// Exports the architecture of a trained model along with these settings
+    pub fn with_model(&self, model: &Sequential) -> ExperimentConfig {
+        ExperimentConfig {
+            model: ModelConfig::from_model(model),
+            ..self.clone()
+        }
+    }
+
+    pub fn build_optimizer(&self) -> Result<Box<dyn Optimizer>, HALError> {
+        let params = self
+            .optimizer
+            .params
+            .iter()
+            .map(|(k, v)| (k.clone(), v.to_string()))
+            .collect::<Vec<(String, String)>>();
+        let params = params
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect::<HashMap<&str, &str>>();
+        optimizer::get_optimizer(&self.optimizer.name, &params)
+    }
+
+    /// Builds the model with its optimizer, ready to `fit`
+    ///
+    /// The weights are initialized reproducibly when `training.seed` is set.
+    /// Invalid layers, metrics or optimizer settings are a PARSE_ERROR.
+    pub fn build_model(&self) -> Result<Sequential, HALError> {
+        if self.model.layers.is_empty() {
+            return Err(HALError::PARSE_ERROR);
+        }
+        let optimizer = self.build_optimizer().map_err(|_| HALError::PARSE_ERROR)?;
+        let mut model = Sequential::new(optimizer, &self.model.loss);
+        for metric in &self.model.metrics {
+            model
+                .add_metric(metric)
+                .map_err(|_| HALError::PARSE_ERROR)?;
+        }
+
+        initializations::set_seed(self.training.seed);
+        let added = self
+            .model
+            .layers
+            .iter()
+            .try_for_each(|spec| model.try_add_config(&spec.to_layer_config()));
+        initializations::set_seed(None);
+        added.map(|_| model)
+    }
+
+    /// Builds the data source of the `data` table, with its fitted transforms
+    pub fn build_source(&self) -> Result<BuiltSource, HALError> {
+        let data = self.data.as_ref().ok_or(HALError::PARSE_ERROR)?;
+        let seed = self
+            .training
+            .seed
+            .unwrap_or_else(initializations::next_seed);
+        data.build(self.training.batch_size, seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    const CONFIG: &str = r#"
+[model]
+loss = "mse"
+metrics = ["mae"]
+
+[[model.layers]]
+layer = "dense"
+name = "encoder"
+activation = "tanh"
+input_size = 8
+output_size = 2
+w_init = "uniform"
+b_init = "zeros"
+contractive_weight = 1.0
+
+[[model.layers]]
+layer = "dense"
+activation = "sigmoid"
+input_size = 2
+output_size = 8
+w_init = "uniform"
+b_init = "zeros"
+
+[optimizer]
+name = "sgd"
+learning_rate = 0.01
+momentum = 0.9
+
+[data]
+source = "sin"
+input_size = 8
+num_samples = 64
+
+[training]
+epochs = 2
+batch_size = 16
+seed = 7
+"#;
+
+    #[test]
+    fn build_and_round_trip() {
+        let config = ExperimentConfig::from_toml(CONFIG).unwrap();
+        let model = config.build_model().unwrap();
+        assert_eq!(model.layer_index("encoder"), Some(0));
+        assert!((model.optimizer().learning_rate() - 0.01).abs() < 1e-7);
+        let (source, transforms) = config.build_source().unwrap();
+        assert_eq!(source.info().num_samples, 64);
+        assert!(transforms.is_none());
+
+        // the exported architecture reads back to the same config, typed values included
+        let exported = config.with_model(&model);
+        assert_eq!(exported, config);
+        assert_eq!(
+            exported.model.layers[0].params["contractive_weight"],
+            ParamValue::Float(1.0)
+        );
+        let toml = exported.to_toml().unwrap();
+        assert_eq!(ExperimentConfig::from_toml(&toml).unwrap(), config);
+        let json = exported.to_json().unwrap();
+        assert_eq!(ExperimentConfig::from_json(&json).unwrap(), config);
+    }
+
+    #[test]
+    fn transforms_are_fitted_on_the_training_data() {
+        let scaled = CONFIG.replace(
+            "num_samples = 64\n",
+            "num_samples = 64\ntransform = \"minmax\"\n",
+        );
+        let config = ExperimentConfig::from_toml(&scaled).unwrap();
+        let (source, transforms) = config.build_source().unwrap();
+        assert!(transforms.is_some());
+        let values = utils::array_to_vec(&source.get_all().unwrap().input);
+        assert!(values.iter().all(|v| *v >= -1e-6 && *v <= 1.0 + 1e-6));
+
+        let unknown = scaled.replace("minmax", "cubic");
+        let config = ExperimentConfig::from_toml(&unknown).unwrap();
+        assert!(matches!(config.build_source(), Err(HALError::PARSE_ERROR)));
+    }
+
+    #[test]
+    fn invalid_configs() {
+        let unknown_param = CONFIG.replace("momentum", "momentun");
+        let config = ExperimentConfig::from_toml(&unknown_param).unwrap();
+        assert!(config.build_model().is_err());
+
+        let missing_size = CONFIG.replace("input_size = 2\n", "");
+        let config = ExperimentConfig::from_toml(&missing_size).unwrap();
+        assert!(config.build_model().is_err());
+
+        // values are validated instead of panicking in `add`
+        for invalid in [
+            CONFIG.replace("input_size = 2\n", "input_size = \"two\"\n"),
+            CONFIG.replace("activation = \"sigmoid\"", "activation = \"relu\""),
+            CONFIG.replace(
+                "input_size = 2\n",
+                "input_size = 2\ntied_to = \"missing\"\n",
+            ),
+        ]
+        .iter()
+        {
+            let config = ExperimentConfig::from_toml(invalid).unwrap();
+            assert!(matches!(config.build_model(), Err(HALError::PARSE_ERROR)));
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use af::{self, Array};
+
+use crate::error::HALError;
+
+/// Small value added to the norms to avoid dividing by zero
+const NORM_EPSILON: f32 = 1e-7;
+
+/// Weight constraints that are applied after each optimizer update
+///
+/// Norms are taken per output unit, ie: over the columns of a [input, output] weight.
+#[derive(Clone, Debug)]
+pub enum Constraint {
+    /// Rescales columns whose norm exceeds `max_value`
+    MaxNorm { max_value: f32 },
+    /// Rescales every column to unit norm
+    UnitNorm,
+}
+
+impl Constraint {
+    pub fn apply(&self, w: &Array<f32>) -> Array<f32> {
+        let norms = column_norms(w);
+        match *self {
+            Constraint::MaxNorm { max_value } => {
+                // w * max_value / max(norm, max_value)
+                let scale = af::div(&max_value, &af::maxof(&norms, &max_value, false), false);
+                af::mul(w, &scale, true)
+            }
+            Constraint::UnitNorm => af::div(w, &af::add(&norms, &NORM_EPSILON, false), true),
+        }
+    }
+}
+
+/// Returns the l2 norm of each column as a [1, output] array
+fn column_norms(w: &Array<f32>) -> Array<f32> {
+    af::sqrt(&af::sum(&af::mul(w, w, false), 0))
+}
+
+/// Helper to provide a constraint from a string and the layer params
+pub fn get_constraint(name: &str, params: &HashMap<&str, String>) -> Result<Constraint, HALError> {
+    match name {
+        "max_norm" => Ok(Constraint::MaxNorm {
+            max_value: params
+                .get("max_norm")
+                .ok_or(HALError::UNKNOWN)?
+                .parse::<f32>()
+                .map_err(|_| HALError::UNKNOWN)?,
+        }),
+        "unit_norm" => Ok(Constraint::UnitNorm),
+        _ => Err(HALError::UNKNOWN),
+    }
+}
@@ -0,0 +1,245 @@
+use af::{Array, Dim4};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+
+use crate::data::{Data, DataParams, DataSouce};
+use crate::error::HALError;
+use crate::initializations;
+use crate::utils;
+
+/// The supported input corruption strategies
+#[derive(Clone, Copy, Debug)]
+pub enum Corruption {
+    /// Adds zero mean gaussian noise with the given standard deviation
+    Gaussian { std: f32 },
+    /// Sets each element to zero with probability `rate` (dropout noise)
+    Masking { rate: f32 },
+    /// Sets each element to the minibatch min or max with probability `rate`
+    SaltAndPepper { rate: f32 },
+    /// Zeros one random contiguous block covering `rate` of each sample
+    BlockErasure { rate: f32 },
+}
+
+/// Helper to provide a corruption from a string
+pub fn get_corruption(name: &str, rate: f32) -> Result<Corruption, HALError> {
+    match name {
+        "gaussian" => Ok(Corruption::Gaussian { std: rate }),
+        "masking" => Ok(Corruption::Masking { rate }),
+        "salt_and_pepper" => Ok(Corruption::SaltAndPepper { rate }),
+        "block_erasure" => Ok(Corruption::BlockErasure { rate }),
+        _ => Err(HALError::UNKNOWN),
+    }
+}
+
+/// Wraps a DataSource and corrupts its inputs while keeping the targets clean
+///
+/// This turns any source into a denoising autoencoder source. The
+/// corruption rng is seeded like the weight initializations (see
+/// `initializations::set_seed`), so a seeded run corrupts the same way every
+/// time. `with_seed` overrides the seed of a single source.
+/// Only the train iterator is corrupted unless `corrupt_evaluation` is set,
+/// so validation / test losses stay comparable to clean baselines.
+pub struct CorruptedSource<T: DataSouce> {
+    pub source: T,
+    pub corruption: Corruption,
+    pub corrupt_evaluation: bool,
+    rng: RefCell<StdRng>,
+}
+
+impl<T: DataSouce> CorruptedSource<T> {
+    pub fn new(source: T, corruption: Corruption) -> CorruptedSource<T> {
+        CorruptedSource {
+            source,
+            corruption,
+            corrupt_evaluation: false,
+            rng: RefCell::new(StdRng::seed_from_u64(initializations::next_seed())),
+        }
+    }
+
+    /// Draws the corruption from an rng seeded with `seed` instead
+    pub fn with_seed(self, seed: u64) -> CorruptedSource<T> {
+        self.rng.replace(StdRng::seed_from_u64(seed));
+        self
+    }
+
+    /// Also corrupts the validation & test iterators (eg: to measure denoising)
+    pub fn with_evaluation_corruption(mut self) -> CorruptedSource<T> {
+        self.corrupt_evaluation = true;
+        self
+    }
+
+    fn corrupt_evaluation_batch(&self, batch: Data) -> Data {
+        if !self.corrupt_evaluation {
+            return batch;
+        }
+        Data {
+            input: self.corrupt(&batch.input),
+            target: batch.target,
+            labels: batch.labels,
+        }
+    }
+
+    /// Returns a corrupted copy of the provided inputs
+    pub fn corrupt(&self, input: &Array<f32>) -> Array<f32> {
+        let dims = input.dims();
+        let mut values = utils::array_to_vec(input);
+        let mut rng = self.rng.borrow_mut();
+
+        match self.corruption {
+            Corruption::Gaussian { std } => {
+                for v in values.iter_mut() {
+                    *v += std * utils::gaussian(&mut *rng);
+                }
+            }
+            Corruption::Masking { rate } => {
+                for v in values.iter_mut() {
+                    if rng.gen::<f32>() < rate {
+                        *v = 0.0;
+                    }
+                }
+            }
+            Corruption::SaltAndPepper { rate } => {
+                let low = values.iter().cloned().fold(f32::INFINITY, f32::min);
+                let high = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                for v in values.iter_mut() {
+                    if rng.gen::<f32>() < rate {
+                        *v = if rng.gen::<bool>() { high } else { low };
+                    }
+                }
+            }
+            Corruption::BlockErasure { rate } => {
+                erase_blocks(&mut values, dims, rate, &mut *rng);
+            }
+        }
+
+        utils::vec_to_array(values, dims)
+    }
+}
+
+/// Zeros a random block per sample of a column major [batch, d1, d2, d3] buffer
+///
+/// For 2d samples ([batch, feature]) the block is a contiguous run of features,
+/// otherwise it is a rectangle over dims 1 & 2 spanning all of dim 3.
+fn erase_blocks(values: &mut [f32], dims: Dim4, rate: f32, rng: &mut StdRng) {
+    let rate = rate.max(0.0).min(1.0);
+    let (n, d1, d2, d3) = (
+        dims[0] as usize,
+        dims[1] as usize,
+        dims[2] as usize,
+        dims[3] as usize,
+    );
+    let (h, w) = if d2 == 1 {
+        (((rate * d1 as f32).ceil() as usize).min(d1), 1)
+    } else {
+        let side = rate.sqrt();
+        (
+            ((side * d1 as f32).ceil() as usize).min(d1),
+            ((side * d2 as f32).ceil() as usize).min(d2),
+        )
+    };
+    if h == 0 || w == 0 {
+        return;
+    }
+
+    for b in 0..n {
+        let start_i = rng.gen_range(0..=(d1 - h));
+        let start_j = rng.gen_range(0..=(d2 - w));
+        for k in 0..d3 {
+            for j in start_j..(start_j + w) {
+                for i in start_i..(start_i + h) {
+                    values[b + n * (i + d1 * (j + d2 * k))] = 0.0;
+                }
+            }
+        }
+    }
+}
+
+impl<T: DataSouce> DataSouce for CorruptedSource<T> {
+    fn info(&self) -> DataParams {
+        self.source.info()
+    }
+
+    fn get_train_iter(&self, num_batch: u64) -> Data {
+        let batch = self.source.get_train_iter(num_batch);
+        Data {
+            input: self.corrupt(&batch.input),
+            target: batch.target,
+            labels: batch.labels,
+        }
+    }
+
+    fn get_test_iter(&self, num_batch: u64) -> Data {
+        self.corrupt_evaluation_batch(self.source.get_test_iter(num_batch))
+    }
+
+    fn num_batches(&self, batch_size: u64) -> u64 {
+        self.source.num_batches(batch_size)
+    }
+
+    fn get_validation_iter(&self, num_batch: u64) -> Option<Data> {
+        self.source
+            .get_validation_iter(num_batch)
+            .map(|batch| self.corrupt_evaluation_batch(batch))
+    }
+
+    fn num_validation_samples(&self) -> u64 {
+        self.source.num_validation_samples()
+    }
+
+    fn num_test_samples(&self) -> u64 {
+        self.source.num_test_samples()
+    }
+
+    fn shutdown(&self) {
+        self.source.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::SinSource;
+    use af::DType;
+
+    #[test]
+    fn same_seed_same_corruption() {
+        let corruption = Corruption::Masking { rate: 0.5 };
+        initializations::set_seed(Some(42));
+        let a = CorruptedSource::new(SinSource::new(8, 4, DType::F32, 16), corruption);
+        initializations::set_seed(Some(42));
+        let b = CorruptedSource::new(SinSource::new(8, 4, DType::F32, 16), corruption);
+        initializations::set_seed(None);
+        let batch_a = a.get_train_iter(4);
+        let batch_b = b.get_train_iter(4);
+        assert_eq!(
+            utils::array_to_vec(&batch_a.input),
+            utils::array_to_vec(&batch_b.input)
+        );
+        // targets are left untouched
+        assert_ne!(
+            utils::array_to_vec(&batch_a.input),
+            utils::array_to_vec(&batch_a.target)
+        );
+    }
+
+    #[test]
+    fn evaluation_corruption_is_opt_in() {
+        let corruption = Corruption::Masking { rate: 0.5 };
+        let clean = CorruptedSource::new(SinSource::new(8, 4, DType::F32, 16), corruption);
+        let batch = clean.get_test_iter(4);
+        assert_eq!(
+            utils::array_to_vec(&batch.input),
+            utils::array_to_vec(&batch.target)
+        );
+
+        let noisy = CorruptedSource::new(SinSource::new(8, 4, DType::F32, 16), corruption)
+            .with_seed(42)
+            .with_evaluation_corruption();
+        let batch = noisy.get_test_iter(4);
+        assert_ne!(
+            utils::array_to_vec(&batch.input),
+            utils::array_to_vec(&batch.target)
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use af::{Array, DType, Dim4};
+use std::cell::Cell;
+
+use crate::data::{self, Data, DataParams, DataSouce};
+use crate::error::HALError;
+
+/// Options used to read a CSV/TSV table
+///
+/// Columns are given by header name or by (0 based) index. When
+/// `target_columns` is `None` the source runs in autoencoder mode (target = input).
+#[derive(Clone, Debug)]
+pub struct CsvParams {
+    pub delimiter: u8,
+    pub has_header: bool,
+    pub input_columns: Option<Vec<String>>,
+    pub target_columns: Option<Vec<String>>,
+}
+
+impl Default for CsvParams {
+    fn default() -> Self {
+        CsvParams {
+            delimiter: b',',
+            has_header: true,
+            input_columns: None,
+            target_columns: None,
+        }
+    }
+}
+
+impl CsvParams {
+    /// Default params for tab separated files
+    pub fn tsv() -> Self {
+        CsvParams {
+            delimiter: b'\t',
+            ..CsvParams::default()
+        }
+    }
+}
+
+/// A DataSource serving minibatches of a numeric CSV/TSV table as [batch, features]
+pub struct CsvSource {
+    pub params: DataParams,
+    pub input: Array<f32>,
+    pub target: Array<f32>,
+    pub header: Vec<String>,
+    train_position: Cell<u64>,
+    test_position: Cell<u64>,
+}
+
+impl CsvSource {
+    pub fn new(path: &str, batch_size: u64, csv_params: CsvParams) -> Result<CsvSource, HALError> {
+        let (header, rows) = read_csv(path, csv_params.delimiter, csv_params.has_header)?;
+        let num_samples = rows.len() as u64;
+        if num_samples == 0 {
+            return Err(HALError::PARSE_ERROR);
+        }
+
+        let input_columns = resolve_columns(&header, csv_params.input_columns.as_ref())?;
+        let input = select_columns(&rows, &input_columns);
+        let (target, num_targets) = match csv_params.target_columns.as_ref() {
+            Some(columns) => {
+                let target_columns = resolve_columns(&header, Some(columns))?;
+                (select_columns(&rows, &target_columns), target_columns.len())
+            }
+            None => (input.copy(), input_columns.len()),
+        };
+
+        Ok(CsvSource {
+            params: DataParams {
+                input_dims: Dim4::new(&[batch_size, input_columns.len() as u64, 1, 1]),
+                target_dims: Dim4::new(&[batch_size, num_targets as u64, 1, 1]),
+                dtypes: DType::F32,
+                num_samples,
+            },
+            input,
+            target,
+            header,
+            train_position: Cell::new(0),
+            test_position: Cell::new(0),
+        })
+    }
+
+    fn get_batch(&self, position: &Cell<u64>, num_batch: u64) -> Data {
+        let indices = data::next_indices(position, self.params.num_samples, num_batch);
+        Data {
+            input: data::gather_rows(&self.input, &indices),
+            target: data::gather_rows(&self.target, &indices),
+            labels: None,
+        }
+    }
+}
+
+impl DataSouce for CsvSource {
+    fn info(&self) -> DataParams {
+        self.params.clone()
+    }
+
+    fn get_train_iter(&self, num_batch: u64) -> Data {
+        self.get_batch(&self.train_position, num_batch)
+    }
+
+    fn get_test_iter(&self, num_batch: u64) -> Data {
+        self.get_batch(&self.test_position, num_batch)
+    }
+
+    fn get_all(&self) -> Option<Data> {
+        Some(Data {
+            input: self.input.copy(),
+            target: self.target.copy(),
+            labels: None,
+        })
+    }
+}
+
+/// Reads a numeric table and returns its header (column indices when there is none) & rows
+pub(crate) fn read_csv(
+    path: &str,
+    delimiter: u8,
+    has_header: bool,
+) -> Result<(Vec<String>, Vec<Vec<f32>>), HALError> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(has_header)
+        .trim(::csv::Trim::All)
+        .from_path(path)
+        .map_err(|_| HALError::IO_ERROR)?;
+
+    let mut rows: Vec<Vec<f32>> = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|_| HALError::PARSE_ERROR)?;
+        let row = record
+            .iter()
+            .map(|field| field.parse::<f32>().map_err(|_| HALError::PARSE_ERROR))
+            .collect::<Result<Vec<f32>, HALError>>()?;
+        if rows.first().map_or(false, |first| first.len() != row.len()) {
+            return Err(HALError::PARSE_ERROR);
+        }
+        rows.push(row);
+    }
+
+    let num_columns = rows.first().map(|r| r.len()).unwrap_or(0);
+    let header = if has_header {
+        reader
+            .headers()
+            .map_err(|_| HALError::PARSE_ERROR)?
+            .iter()
+            .map(|h| h.to_string())
+            .collect()
+    } else {
+        (0..num_columns).map(|i| i.to_string()).collect()
+    };
+
+    Ok((header, rows))
+}
+
+/// Maps column names (or indices) to indices, `None` selects every column
+pub(crate) fn resolve_columns(
+    header: &[String],
+    columns: Option<&Vec<String>>,
+) -> Result<Vec<usize>, HALError> {
+    match columns {
+        None => Ok((0..header.len()).collect()),
+        Some(columns) => columns
+            .iter()
+            .map(|column| match header.iter().position(|h| h == column) {
+                Some(index) => Ok(index),
+                None => match column.parse::<usize>() {
+                    Ok(index) if index < header.len() => Ok(index),
+                    _ => Err(HALError::UNKNOWN),
+                },
+            })
+            .collect(),
+    }
+}
+
+/// Builds a [rows, columns] Array of the selected columns
+pub(crate) fn select_columns(rows: &[Vec<f32>], columns: &[usize]) -> Array<f32> {
+    let values = rows
+        .iter()
+        .flat_map(|row| columns.iter().map(move |&c| row[c]))
+        .collect::<Vec<f32>>();
+    data::row_major_to_array(&values, &[rows.len() as u64, columns.len() as u64])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn read_csv_with_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("read.csv");
+        let mut file = std::fs::File::create(&path).unwrap();
+        writeln!(file, "a,b,label\n1.0,2.0,0\n3.0,4.0,1").unwrap();
+
+        let (header, rows) = read_csv(path.to_str().unwrap(), b',', true).unwrap();
+        assert_eq!(header, vec!["a", "b", "label"]);
+        assert_eq!(rows, vec![vec![1.0, 2.0, 0.0], vec![3.0, 4.0, 1.0]]);
+
+        let columns = vec!["label".to_string(), "0".to_string()];
+        assert_eq!(
+            resolve_columns(&header, Some(&columns)).unwrap(),
+            vec![2, 0]
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use af::{Array, DType, Dim4};
+use flate2::read::GzDecoder;
+use std::cell::Cell;
+use std::fs::File;
+use std::io::Read;
+
+use crate::data::{self, Data, DataParams, DataSouce};
+use crate::error::HALError;
+
+/// Magic bytes of a gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// A DataSource serving images stored in the IDX format (MNIST, Fashion-MNIST)
+///
+/// Pixels are scaled to [0, 1]. Minibatches are either flattened to
+/// [batch, h * w * c] or kept as [batch, h, w, c].
+pub struct IdxSource {
+    pub params: DataParams,
+    pub input: Array<f32>,
+    pub target: Array<f32>,
+    train_position: Cell<u64>,
+    test_position: Cell<u64>,
+}
+
+impl IdxSource {
+    pub fn new(path: &str, batch_size: u64, flatten: bool) -> Result<IdxSource, HALError> {
+        let (shape, values) = read_idx(path)?;
+        if shape.len() < 2 || shape.len() > 4 {
+            return Err(HALError::PARSE_ERROR);
+        }
+
+        // [n, h, w, c] with missing trailing dims set to 1
+        let mut dims = [1u64; 4];
+        dims[..shape.len()].copy_from_slice(&shape);
+        let num_samples = dims[0];
+        if num_samples == 0 {
+            return Err(HALError::PARSE_ERROR);
+        }
+        let sample_dims = if flatten {
+            Dim4::new(&[batch_size, dims[1] * dims[2] * dims[3], 1, 1])
+        } else {
+            Dim4::new(&[batch_size, dims[1], dims[2], dims[3]])
+        };
+
+        let input = if flatten {
+            data::row_major_to_array(&values, &[num_samples, sample_dims[1]])
+        } else {
+            data::row_major_to_array(&values, &dims)
+        };
+
+        Ok(IdxSource {
+            params: DataParams {
+                input_dims: sample_dims,
+                target_dims: sample_dims,
+                dtypes: DType::F32,
+                num_samples,
+            },
+            target: input.copy(),
+            input,
+            train_position: Cell::new(0),
+            test_position: Cell::new(0),
+        })
+    }
+
+    fn get_batch(&self, position: &Cell<u64>, num_batch: u64) -> Data {
+        let indices = data::next_indices(position, self.params.num_samples, num_batch);
+        Data {
+            input: data::gather_rows(&self.input, &indices),
+            target: data::gather_rows(&self.target, &indices),
+            labels: None,
+        }
+    }
+}
+
+impl DataSouce for IdxSource {
+    fn info(&self) -> DataParams {
+        self.params.clone()
+    }
+
+    fn get_train_iter(&self, num_batch: u64) -> Data {
+        self.get_batch(&self.train_position, num_batch)
+    }
+
+    fn get_test_iter(&self, num_batch: u64) -> Data {
+        self.get_batch(&self.test_position, num_batch)
+    }
+
+    fn get_all(&self) -> Option<Data> {
+        Some(Data {
+            input: self.input.copy(),
+            target: self.target.copy(),
+            labels: None,
+        })
+    }
+}
+
+/// Reads a (optionally gzip compressed) IDX file and returns its shape & row major values
+///
+/// Unsigned / signed bytes are scaled to [0, 1], wider types are returned as is.
+pub fn read_idx(path: &str) -> Result<(Vec<u64>, Vec<f32>), HALError> {
+    let mut raw = Vec::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_end(&mut raw))
+        .map_err(|_| HALError::IO_ERROR)?;
+
+    let bytes = if raw.starts_with(&GZIP_MAGIC) {
+        let mut decoded = Vec::new();
+        GzDecoder::new(&raw[..])
+            .read_to_end(&mut decoded)
+            .map_err(|_| HALError::PARSE_ERROR)?;
+        decoded
+    } else {
+        raw
+    };
+
+    // magic: [0, 0, type, ndims] followed by ndims big endian u32 dims
+    if bytes.len() < 4 || bytes[0] != 0 || bytes[1] != 0 {
+        return Err(HALError::PARSE_ERROR);
+    }
+    let type_code = bytes[2];
+    let ndims = bytes[3] as usize;
+    let header_len = 4 + 4 * ndims;
+    if bytes.len() < header_len {
+        return Err(HALError::PARSE_ERROR);
+    }
+    let shape = bytes[4..header_len]
+        .chunks(4)
+        .map(|d| u32::from_be_bytes([d[0], d[1], d[2], d[3]]) as u64)
+        .collect::<Vec<u64>>();
+    let num_elements = shape.iter().product::<u64>() as usize;
+
+    let body = &bytes[header_len..];
+    let width = match type_code {
+        0x08 | 0x09 => 1,
+        0x0B => 2,
+        0x0C | 0x0D => 4,
+        0x0E => 8,
+        _ => return Err(HALError::PARSE_ERROR),
+    };
+    if body.len() < num_elements * width {
+        return Err(HALError::PARSE_ERROR);
+    }
+
+    let values = body[..num_elements * width]
+        .chunks(width)
+        .map(|v| match type_code {
+            0x08 => v[0] as f32 / 255.0,
+            0x09 => (v[0] as i8 as f32 + 128.0) / 255.0,
+            0x0B => i16::from_be_bytes([v[0], v[1]]) as f32,
+            0x0C => i32::from_be_bytes([v[0], v[1], v[2], v[3]]) as f32,
+            0x0D => f32::from_be_bytes([v[0], v[1], v[2], v[3]]),
+            _ => f64::from_be_bytes([v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]]) as f32,
+        })
+        .collect::<Vec<f32>>();
+
+    Ok((shape, values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    /// Builds a tiny [num_samples, 2, 3] ubyte IDX file
+    fn idx_bytes(num_samples: u32) -> Vec<u8> {
+        let mut bytes = vec![0u8, 0, 0x08, 3];
+        for d in [num_samples, 2, 3].iter() {
+            bytes.extend_from_slice(&d.to_be_bytes());
+        }
+        bytes.extend((0..num_samples * 6).map(|v| (v * 20) as u8));
+        bytes
+    }
+
+    fn tiny_idx() -> Vec<u8> {
+        idx_bytes(2)
+    }
+
+    #[test]
+    fn read_raw_and_gzip_idx() {
+        let dir = tempfile::tempdir().unwrap();
+        let raw_path = dir.path().join("tiny.idx");
+        std::fs::write(&raw_path, tiny_idx()).unwrap();
+
+        let gz_path = dir.path().join("tiny.idx.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tiny_idx()).unwrap();
+        std::fs::write(&gz_path, encoder.finish().unwrap()).unwrap();
+
+        for path in [raw_path, gz_path].iter() {
+            let (shape, values) = read_idx(path.to_str().unwrap()).unwrap();
+            assert_eq!(shape, vec![2, 2, 3]);
+            assert_eq!(values.len(), 12);
+            assert_eq!(values[0], 0.0);
+            assert!((values[11] - 220.0 / 255.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn image_shaped_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("batches.idx");
+        std::fs::write(&path, tiny_idx()).unwrap();
+
+        let source = IdxSource::new(path.to_str().unwrap(), 2, false).unwrap();
+        let batch = source.get_train_iter(2);
+        assert_eq!(batch.input.dims(), Dim4::new(&[2, 2, 3, 1]));
+
+        let source = IdxSource::new(path.to_str().unwrap(), 2, true).unwrap();
+        let batch = source.get_train_iter(2);
+        assert_eq!(batch.input.dims(), Dim4::new(&[2, 6, 1, 1]));
+    }
+
+    #[test]
+    fn empty_idx_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty.idx");
+        std::fs::write(&path, idx_bytes(0)).unwrap();
+
+        assert!(matches!(
+            IdxSource::new(path.to_str().unwrap(), 2, true),
+            Err(HALError::PARSE_ERROR)
+        ));
+    }
+}
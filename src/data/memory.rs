@@ -0,0 +1,212 @@
+use af::{Array, DType, Dim4};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::cell::{Cell, RefCell};
+
+use crate::data::{self, Data, DataParams, DataSouce};
+
+/// A finite DataSource over input/target Arrays held in memory
+///
+/// Samples are taken along dim 0. The train iterator walks one epoch at a
+/// time, reshuffling the sample order (with an rng seeded by `seed`) at the
+/// start of every epoch. The remainder of an epoch is either served as a
+/// final partial batch or dropped (`drop_last`), in which case a batch size
+/// larger than the number of samples is rejected. The test iterator walks the
+/// samples in order and always serves the final partial batch.
+pub struct InMemorySource {
+    pub params: DataParams,
+    pub input: Array<f32>,
+    pub target: Array<f32>,
+    pub shuffle: bool,
+    pub drop_last: bool,
+    pub labels: Option<Array<f32>>,
+    rng: RefCell<StdRng>,
+    order: RefCell<Vec<u32>>,
+    train_position: Cell<u64>,
+    test_position: Cell<u64>,
+}
+
+impl InMemorySource {
+    pub fn new(
+        input: Array<f32>,
+        target: Array<f32>,
+        batch_size: u64,
+        shuffle: bool,
+        drop_last: bool,
+        seed: u64,
+    ) -> InMemorySource {
+        let (idims, tdims) = (input.dims(), target.dims());
+        let num_samples = idims[0];
+        assert!(
+            tdims[0] == num_samples,
+            "inputs and targets must have the same number of samples"
+        );
+
+        InMemorySource {
+            params: DataParams {
+                input_dims: Dim4::new(&[batch_size, idims[1], idims[2], idims[3]]),
+                target_dims: Dim4::new(&[batch_size, tdims[1], tdims[2], tdims[3]]),
+                dtypes: DType::F32,
+                num_samples,
+            },
+            input,
+            target,
+            shuffle,
+            drop_last,
+            labels: None,
+            rng: RefCell::new(StdRng::seed_from_u64(seed)),
+            order: RefCell::new((0..num_samples as u32).collect()),
+            train_position: Cell::new(0),
+            test_position: Cell::new(0),
+        }
+    }
+
+    /// Autoencoder mode: the target is a copy of the input
+    pub fn autoencoder(
+        input: Array<f32>,
+        batch_size: u64,
+        shuffle: bool,
+        drop_last: bool,
+        seed: u64,
+    ) -> InMemorySource {
+        let target = input.copy();
+        InMemorySource::new(input, target, batch_size, shuffle, drop_last, seed)
+    }
+
+    /// With `drop_last` a batch larger than the source would drop every sample
+    fn check_batch_size(&self, batch_size: u64) {
+        assert!(
+            !self.drop_last || batch_size <= self.params.num_samples,
+            "drop_last with a batch size of {} would drop all {} samples",
+            batch_size,
+            self.params.num_samples
+        );
+    }
+
+    /// Attaches per-sample ground truth labels, served along with the batches
+    pub fn with_labels(mut self, labels: Array<f32>) -> InMemorySource {
+        assert!(
+            labels.dims()[0] == self.params.num_samples,
+            "labels and inputs must have the same number of samples"
+        );
+        self.labels = Some(labels);
+        self
+    }
+
+    fn gather(&self, indices: &[u32]) -> Data {
+        Data {
+            input: data::gather_rows(&self.input, indices),
+            target: data::gather_rows(&self.target, indices),
+            labels: self
+                .labels
+                .as_ref()
+                .map(|labels| data::gather_rows(labels, indices)),
+        }
+    }
+}
+
+impl DataSouce for InMemorySource {
+    fn info(&self) -> DataParams {
+        self.params.clone()
+    }
+
+    fn get_train_iter(&self, num_batch: u64) -> Data {
+        self.check_batch_size(num_batch);
+        let num_samples = self.params.num_samples;
+        let start = self.train_position.get();
+        if start == 0 && self.shuffle {
+            self.order.borrow_mut().shuffle(&mut *self.rng.borrow_mut());
+        }
+
+        let end = (start + num_batch).min(num_samples);
+        let epoch_done = end == num_samples || (self.drop_last && num_samples - end < num_batch);
+        self.train_position.set(if epoch_done { 0 } else { end });
+
+        let indices = self.order.borrow()[start as usize..end as usize].to_vec();
+        self.gather(&indices)
+    }
+
+    fn get_test_iter(&self, num_batch: u64) -> Data {
+        let num_samples = self.params.num_samples;
+        let start = self.test_position.get();
+        let end = (start + num_batch).min(num_samples);
+        self.test_position.set(end % num_samples);
+
+        let indices = (start as u32..end as u32).collect::<Vec<u32>>();
+        self.gather(&indices)
+    }
+
+    fn num_batches(&self, batch_size: u64) -> u64 {
+        self.check_batch_size(batch_size);
+        let num_samples = self.params.num_samples;
+        if self.drop_last {
+            num_samples / batch_size
+        } else {
+            (num_samples + batch_size - 1) / batch_size
+        }
+    }
+
+    fn get_all(&self) -> Option<Data> {
+        Some(Data {
+            input: self.input.copy(),
+            target: self.target.copy(),
+            labels: self.labels.as_ref().map(|labels| labels.copy()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    fn source(drop_last: bool, seed: u64) -> InMemorySource {
+        let input =
+            utils::vec_to_array((0..5).map(|v| v as f32).collect(), Dim4::new(&[5, 1, 1, 1]));
+        InMemorySource::autoencoder(input, 2, true, drop_last, seed)
+    }
+
+    #[test]
+    fn partial_last_batch() {
+        let src = source(false, 1);
+        assert_eq!(src.num_batches(2), 3);
+
+        let sizes = (0..3)
+            .map(|_| src.get_train_iter(2).input.dims()[0])
+            .collect::<Vec<u64>>();
+        assert_eq!(sizes, vec![2, 2, 1]);
+    }
+
+    #[test]
+    fn drop_last_and_reshuffle() {
+        let src = source(true, 1);
+        assert_eq!(src.num_batches(2), 2);
+
+        let epochs = |src: &InMemorySource| {
+            (0..5)
+                .map(|_| {
+                    let mut epoch = Vec::new();
+                    for _ in 0..2 {
+                        let batch = src.get_train_iter(2);
+                        assert_eq!(batch.input.dims()[0], 2);
+                        epoch.extend(utils::array_to_vec(&batch.input));
+                    }
+                    epoch
+                })
+                .collect::<Vec<Vec<f32>>>()
+        };
+        let orders = epochs(&src);
+
+        // the order is reshuffled between epochs
+        assert!(orders.iter().any(|order| order != &orders[0]));
+        // the same seed yields the same epoch orders
+        assert_eq!(orders, epochs(&source(true, 1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "drop_last with a batch size of 8 would drop all 5 samples")]
+    fn drop_last_rejects_batches_larger_than_the_source() {
+        source(true, 1).num_batches(8);
+    }
+}
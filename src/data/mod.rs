@@ -1,11 +1,35 @@
 use af::{Array, DType, Dim4};
+use std::cell::Cell;
 
+pub use self::corrupt::{get_corruption, CorruptedSource, Corruption};
+pub use self::csv::{CsvParams, CsvSource};
+pub use self::idx::{read_idx, IdxSource};
+pub use self::memory::InMemorySource;
+pub use self::npy::{read_npy, read_npz, NpySource};
+pub use self::prefetch::Prefetcher;
 pub use self::sin::SinSource;
+pub use self::split::{split, SplitSource, SplitStrategy, Stratify};
+pub use self::synthetic::{Anomalies, Generator, SyntheticSource};
+pub(crate) use self::transform::percentile;
+pub use self::transform::{get_transform_kind, Transform, TransformKind, TransformedSource};
+pub use self::window::{WindowMode, WindowParams, WindowSource};
+mod corrupt;
+mod csv;
+mod idx;
+mod memory;
+mod npy;
+mod prefetch;
 mod sin;
+mod split;
+mod synthetic;
+mod transform;
+mod window;
 
 pub struct Data {
     pub input: Array<f32>,
     pub target: Array<f32>,
+    /// Ground truth per-sample labels (eg: class or anomaly flag) if known
+    pub labels: Option<Array<f32>>,
 }
 
 #[derive(Clone)]
@@ -26,4 +50,109 @@ pub trait DataSouce {
     fn info(&self) -> DataParams;
     fn get_train_iter(&self, num_batch: u64) -> Data;
     fn get_test_iter(&self, num_batch: u64) -> Data;
+
+    /// Number of train minibatches that make up one epoch
+    fn num_batches(&self, batch_size: u64) -> u64 {
+        self.info().num_samples / batch_size
+    }
+
+    /// Returns the next validation minibatch (None when there is no validation set)
+    fn get_validation_iter(&self, _num_batch: u64) -> Option<Data> {
+        None
+    }
+
+    /// Number of samples served by one pass of the validation iterator
+    fn num_validation_samples(&self) -> u64 {
+        0
+    }
+
+    /// Number of samples served by one pass of the test iterator
+    fn num_test_samples(&self) -> u64 {
+        self.info().num_samples
+    }
+
+    /// Returns every sample at once, only finite sources provide this
+    fn get_all(&self) -> Option<Data> {
+        None
+    }
+
+    /// Releases the resources held for training (eg: worker threads), called at the end of fit
+    fn shutdown(&self) {}
+}
+
+/// Boxed sources (eg: picked at runtime from a config) are sources too
+impl<T: DataSouce + ?Sized> DataSouce for Box<T> {
+    fn info(&self) -> DataParams {
+        (**self).info()
+    }
+
+    fn get_train_iter(&self, num_batch: u64) -> Data {
+        (**self).get_train_iter(num_batch)
+    }
+
+    fn get_test_iter(&self, num_batch: u64) -> Data {
+        (**self).get_test_iter(num_batch)
+    }
+
+    fn num_batches(&self, batch_size: u64) -> u64 {
+        (**self).num_batches(batch_size)
+    }
+
+    fn get_validation_iter(&self, num_batch: u64) -> Option<Data> {
+        (**self).get_validation_iter(num_batch)
+    }
+
+    fn num_validation_samples(&self) -> u64 {
+        (**self).num_validation_samples()
+    }
+
+    fn num_test_samples(&self) -> u64 {
+        (**self).num_test_samples()
+    }
+
+    fn get_all(&self) -> Option<Data> {
+        (**self).get_all()
+    }
+
+    fn shutdown(&self) {
+        (**self).shutdown()
+    }
+}
+
+/// Build a column major Array from row major (C order) host values of up to 4 dims
+pub(crate) fn row_major_to_array(values: &[f32], shape: &[u64]) -> Array<f32> {
+    assert!(shape.len() <= 4, "at most 4 dimensions are supported");
+    let mut d = [1u64; 4];
+    d[..shape.len()].copy_from_slice(shape);
+    let (s0, s1, s2, s3) = (d[0] as usize, d[1] as usize, d[2] as usize, d[3] as usize);
+    assert!(values.len() == s0 * s1 * s2 * s3);
+
+    let mut col_major = vec![0.0f32; values.len()];
+    for i0 in 0..s0 {
+        for i1 in 0..s1 {
+            for i2 in 0..s2 {
+                for i3 in 0..s3 {
+                    let src = ((i0 * s1 + i1) * s2 + i2) * s3 + i3;
+                    let dst = i0 + s0 * (i1 + s1 * (i2 + s2 * i3));
+                    col_major[dst] = values[src];
+                }
+            }
+        }
+    }
+    Array::new(&col_major, Dim4::new(&d))
+}
+
+/// Gathers the given samples (rows along dim 0) of an Array
+pub(crate) fn gather_rows(values: &Array<f32>, indices: &[u32]) -> Array<f32> {
+    let idx = Array::new(indices, Dim4::new(&[indices.len() as u64, 1, 1, 1]));
+    af::lookup(values, &idx, 0)
+}
+
+/// Returns the next `num_batch` sample indices from `position`, wrapping around at the end
+pub(crate) fn next_indices(position: &Cell<u64>, num_samples: u64, num_batch: u64) -> Vec<u32> {
+    let start = position.get();
+    position.set((start + num_batch) % num_samples);
+    (0..num_batch)
+        .map(|i| ((start + i) % num_samples) as u32)
+        .collect()
 }
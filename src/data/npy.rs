@@ -0,0 +1,254 @@
+use af::{Array, DType, Dim4};
+use std::cell::Cell;
+use std::fs::File;
+use std::io::Read;
+
+use crate::data::{self, Data, DataParams, DataSouce};
+use crate::error::HALError;
+
+/// Magic string at the start of every .npy file
+pub(crate) const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+/// A DataSource serving minibatches of f32/f64 arrays stored as .npy (or inside a .npz)
+///
+/// Samples are taken along the first axis. When no target array is
+/// provided the source runs in autoencoder mode (target = input).
+pub struct NpySource {
+    pub params: DataParams,
+    pub input: Array<f32>,
+    pub target: Array<f32>,
+    train_position: Cell<u64>,
+    test_position: Cell<u64>,
+}
+
+impl NpySource {
+    pub fn new(
+        input_path: &str,
+        target_path: Option<&str>,
+        batch_size: u64,
+    ) -> Result<NpySource, HALError> {
+        let input = read_npy(input_path)?;
+        let target = match target_path {
+            Some(path) => read_npy(path)?,
+            None => input.copy(),
+        };
+        NpySource::from_arrays(input, target, batch_size)
+    }
+
+    /// Loads the input (and optional target) arrays stored under the given names of a .npz
+    pub fn from_npz(
+        path: &str,
+        input_name: &str,
+        target_name: Option<&str>,
+        batch_size: u64,
+    ) -> Result<NpySource, HALError> {
+        let input = read_npz(path, input_name)?;
+        let target = match target_name {
+            Some(name) => read_npz(path, name)?,
+            None => input.copy(),
+        };
+        NpySource::from_arrays(input, target, batch_size)
+    }
+
+    fn from_arrays(
+        input: Array<f32>,
+        target: Array<f32>,
+        batch_size: u64,
+    ) -> Result<NpySource, HALError> {
+        let num_samples = input.dims()[0];
+        if target.dims()[0] != num_samples {
+            return Err(HALError::UNKNOWN);
+        }
+
+        let (idims, tdims) = (input.dims(), target.dims());
+        let input_dims = Dim4::new(&[batch_size, idims[1], idims[2], idims[3]]);
+        let target_dims = Dim4::new(&[batch_size, tdims[1], tdims[2], tdims[3]]);
+
+        Ok(NpySource {
+            params: DataParams {
+                input_dims,
+                target_dims,
+                dtypes: DType::F32,
+                num_samples,
+            },
+            input,
+            target,
+            train_position: Cell::new(0),
+            test_position: Cell::new(0),
+        })
+    }
+
+    fn get_batch(&self, position: &Cell<u64>, num_batch: u64) -> Data {
+        let indices = data::next_indices(position, self.params.num_samples, num_batch);
+        Data {
+            input: data::gather_rows(&self.input, &indices),
+            target: data::gather_rows(&self.target, &indices),
+            labels: None,
+        }
+    }
+}
+
+impl DataSouce for NpySource {
+    fn info(&self) -> DataParams {
+        self.params.clone()
+    }
+
+    fn get_train_iter(&self, num_batch: u64) -> Data {
+        self.get_batch(&self.train_position, num_batch)
+    }
+
+    fn get_test_iter(&self, num_batch: u64) -> Data {
+        self.get_batch(&self.test_position, num_batch)
+    }
+
+    fn get_all(&self) -> Option<Data> {
+        Some(Data {
+            input: self.input.copy(),
+            target: self.target.copy(),
+            labels: None,
+        })
+    }
+}
+
+/// Reads a .npy file of f32/f64 values into an Array
+pub fn read_npy(path: &str) -> Result<Array<f32>, HALError> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_end(&mut bytes))
+        .map_err(|_| HALError::IO_ERROR)?;
+    npy_to_array(&bytes)
+}
+
+/// Reads the array stored under `name` (with or without the .npy suffix) of a .npz file
+pub fn read_npz(path: &str, name: &str) -> Result<Array<f32>, HALError> {
+    let file = File::open(path).map_err(|_| HALError::IO_ERROR)?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|_| HALError::PARSE_ERROR)?;
+    let entry_name = if name.ends_with(".npy") {
+        name.to_string()
+    } else {
+        format!("{}.npy", name)
+    };
+
+    let mut bytes = Vec::new();
+    archive
+        .by_name(&entry_name)
+        .map_err(|_| HALError::IO_ERROR)?
+        .read_to_end(&mut bytes)
+        .map_err(|_| HALError::PARSE_ERROR)?;
+    npy_to_array(&bytes)
+}
+
+fn npy_to_array(bytes: &[u8]) -> Result<Array<f32>, HALError> {
+    let (shape, fortran_order, values) = parse_npy(bytes)?;
+    if shape.len() > 4 {
+        return Err(HALError::PARSE_ERROR);
+    }
+
+    if fortran_order {
+        let mut dims = [1u64; 4];
+        dims[..shape.len()].copy_from_slice(&shape);
+        Ok(Array::new(&values, Dim4::new(&dims)))
+    } else {
+        Ok(data::row_major_to_array(&values, &shape))
+    }
+}
+
+/// Parses the raw contents of a .npy file into (shape, fortran_order, values)
+pub(crate) fn parse_npy(bytes: &[u8]) -> Result<(Vec<u64>, bool, Vec<f32>), HALError> {
+    if bytes.len() < 10 || !bytes.starts_with(NPY_MAGIC) {
+        return Err(HALError::PARSE_ERROR);
+    }
+
+    // version 1.x uses a u16 header length, 2.x & 3.x a u32
+    let (header_len, header_start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (
+            u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize,
+            12,
+        ),
+        _ => return Err(HALError::PARSE_ERROR),
+    };
+    let data_start = header_start + header_len;
+    if bytes.len() < data_start {
+        return Err(HALError::PARSE_ERROR);
+    }
+    let header =
+        std::str::from_utf8(&bytes[header_start..data_start]).map_err(|_| HALError::PARSE_ERROR)?;
+
+    let descr = header_value(header, "descr")?
+        .trim_matches(|c: char| c == '\'' || c == '"')
+        .to_string();
+    let fortran_order = header_value(header, "fortran_order")? == "True";
+    let shape = header_value(header, "shape")?
+        .trim_matches(|c: char| c == '(' || c == ')')
+        .split(',')
+        .map(|d| d.trim())
+        .filter(|d| !d.is_empty())
+        .map(|d| d.parse::<u64>().map_err(|_| HALError::PARSE_ERROR))
+        .collect::<Result<Vec<u64>, HALError>>()?;
+    let num_elements = shape.iter().product::<u64>() as usize;
+
+    let body = &bytes[data_start..];
+    let (width, little_endian) = match descr.as_str() {
+        "<f4" => (4, true),
+        ">f4" => (4, false),
+        "<f8" => (8, true),
+        ">f8" => (8, false),
+        _ => return Err(HALError::PARSE_ERROR),
+    };
+    if body.len() < num_elements * width {
+        return Err(HALError::PARSE_ERROR);
+    }
+
+    let values = body[..num_elements * width]
+        .chunks(width)
+        .map(|v| match (width, little_endian) {
+            (4, true) => f32::from_le_bytes([v[0], v[1], v[2], v[3]]),
+            (4, false) => f32::from_be_bytes([v[0], v[1], v[2], v[3]]),
+            (_, true) => {
+                f64::from_le_bytes([v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]]) as f32
+            }
+            (_, false) => {
+                f64::from_be_bytes([v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]]) as f32
+            }
+        })
+        .collect::<Vec<f32>>();
+
+    Ok((shape, fortran_order, values))
+}
+
+/// Extracts the raw value of `key` from the python dict literal of a .npy header
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str, HALError> {
+    let pattern = format!("'{}':", key);
+    let start = header.find(&pattern).ok_or(HALError::PARSE_ERROR)? + pattern.len();
+    let rest = header[start..].trim_start();
+    let end = if rest.starts_with('(') {
+        rest.find(')').ok_or(HALError::PARSE_ERROR)? + 1
+    } else {
+        rest.find(|c: char| c == ',' || c == '}')
+            .ok_or(HALError::PARSE_ERROR)?
+    };
+    Ok(rest[..end].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_c_order_header() {
+        let header = "{'descr': '<f4', 'fortran_order': False, 'shape': (2, 3), }";
+        let mut bytes = NPY_MAGIC.to_vec();
+        bytes.extend_from_slice(&[1, 0]);
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        for v in 0..6 {
+            bytes.extend_from_slice(&(v as f32).to_le_bytes());
+        }
+
+        let (shape, fortran_order, values) = parse_npy(&bytes).unwrap();
+        assert_eq!(shape, vec![2, 3]);
+        assert!(!fortran_order);
+        assert_eq!(values, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+}
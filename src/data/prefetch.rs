@@ -0,0 +1,155 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::data::{Data, DataParams, DataSouce};
+
+/// The worker thread filling the queue of train minibatches
+struct Worker {
+    batch_size: u64,
+    receiver: Receiver<Data>,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+/// Wraps a DataSource and prepares train minibatches on a worker thread
+///
+/// Up to `depth` ready batches are queued ahead of the training loop. The
+/// worker is started by the first `get_train_iter` call and stopped by
+/// `shutdown` (called at the end of `fit`) or when the prefetcher is dropped.
+/// Test & validation batches are served directly from the wrapped source.
+pub struct Prefetcher<T: DataSouce + Send + 'static> {
+    source: Arc<Mutex<T>>,
+    depth: usize,
+    worker: RefCell<Option<Worker>>,
+}
+
+impl<T: DataSouce + Send + 'static> Prefetcher<T> {
+    pub fn new(source: T, depth: usize) -> Prefetcher<T> {
+        assert!(depth > 0, "prefetch depth must be at least 1");
+        Prefetcher {
+            source: Arc::new(Mutex::new(source)),
+            depth,
+            worker: RefCell::new(None),
+        }
+    }
+
+    fn start(&self, batch_size: u64) -> Worker {
+        let (sender, receiver) = mpsc::sync_channel(self.depth);
+        let stop = Arc::new(AtomicBool::new(false));
+        let source = self.source.clone();
+        let worker_stop = stop.clone();
+
+        let handle = thread::spawn(move || {
+            while !worker_stop.load(Ordering::SeqCst) {
+                let batch = source.lock().unwrap().get_train_iter(batch_size);
+                // the receiver is gone, nothing left to prefetch for
+                if sender.send(batch).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Worker {
+            batch_size,
+            receiver,
+            stop,
+            handle,
+        }
+    }
+
+    fn stop(worker: Worker) {
+        worker.stop.store(true, Ordering::SeqCst);
+        // drain the queue so a blocked worker can observe the stop flag,
+        // the channel disconnects once the worker has exited
+        while worker.receiver.recv().is_ok() {}
+        worker.handle.join().expect("prefetch worker panicked");
+    }
+}
+
+impl<T: DataSouce + Send + 'static> DataSouce for Prefetcher<T> {
+    fn info(&self) -> DataParams {
+        self.source.lock().unwrap().info()
+    }
+
+    fn get_train_iter(&self, num_batch: u64) -> Data {
+        let mut worker = self.worker.borrow_mut();
+        if worker.as_ref().map_or(false, |w| w.batch_size != num_batch) {
+            Prefetcher::<T>::stop(worker.take().unwrap());
+        }
+        if worker.is_none() {
+            *worker = Some(self.start(num_batch));
+        }
+
+        worker
+            .as_ref()
+            .unwrap()
+            .receiver
+            .recv()
+            .expect("prefetch worker stopped unexpectedly")
+    }
+
+    fn get_test_iter(&self, num_batch: u64) -> Data {
+        self.source.lock().unwrap().get_test_iter(num_batch)
+    }
+
+    fn num_batches(&self, batch_size: u64) -> u64 {
+        self.source.lock().unwrap().num_batches(batch_size)
+    }
+
+    fn get_validation_iter(&self, num_batch: u64) -> Option<Data> {
+        self.source.lock().unwrap().get_validation_iter(num_batch)
+    }
+
+    fn num_validation_samples(&self) -> u64 {
+        self.source.lock().unwrap().num_validation_samples()
+    }
+
+    fn num_test_samples(&self) -> u64 {
+        self.source.lock().unwrap().num_test_samples()
+    }
+
+    fn get_all(&self) -> Option<Data> {
+        self.source.lock().unwrap().get_all()
+    }
+
+    fn shutdown(&self) {
+        if let Some(worker) = self.worker.borrow_mut().take() {
+            Prefetcher::<T>::stop(worker);
+        }
+        self.source.lock().unwrap().shutdown();
+    }
+}
+
+impl<T: DataSouce + Send + 'static> Drop for Prefetcher<T> {
+    fn drop(&mut self) {
+        if let Some(worker) = self.worker.get_mut().take() {
+            Prefetcher::<T>::stop(worker);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::InMemorySource;
+    use crate::utils;
+    use af::Dim4;
+
+    #[test]
+    fn prefetched_batches_keep_order() {
+        let input =
+            utils::vec_to_array((0..6).map(|v| v as f32).collect(), Dim4::new(&[6, 1, 1, 1]));
+        let source = InMemorySource::autoencoder(input, 2, false, false, 0);
+        let prefetcher = Prefetcher::new(source, 2);
+
+        let mut values = Vec::new();
+        for _ in 0..3 {
+            values.extend(utils::array_to_vec(&prefetcher.get_train_iter(2).input));
+        }
+        prefetcher.shutdown();
+        assert_eq!(values, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+}
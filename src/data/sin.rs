@@ -51,11 +51,18 @@ impl DataSouce for SinSource {
         let batch = Data {
             input: inp.clone(),
             target: inp.copy(),
+            labels: None,
         };
         batch
     }
 
+    /// The wave has no held-out samples, this continues the train wave
     fn get_test_iter(&self, num_batch: u64) -> Data {
         self.get_train_iter(num_batch)
     }
+
+    /// Reported as 0 since the test iterator only serves train data
+    fn num_test_samples(&self) -> u64 {
+        0
+    }
 }
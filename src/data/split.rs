@@ -0,0 +1,257 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::BTreeMap;
+
+use af::Array;
+
+use crate::data::{self, Data, DataParams, DataSouce, InMemorySource};
+use crate::error::HALError;
+use crate::utils;
+
+/// How the samples of a finite source are partitioned
+#[derive(Clone, Debug)]
+pub enum SplitStrategy {
+    /// Random split by fractions, the test set receives the remainder
+    Ratio { train: f32, validation: f32 },
+    /// Explicit sample indices of every partition
+    Indices {
+        train: Vec<u32>,
+        validation: Vec<u32>,
+        test: Vec<u32>,
+    },
+}
+
+/// What the ratio split is stratified by
+#[derive(Clone, Copy, Debug)]
+pub enum Stratify {
+    /// The values of an input column
+    Column(usize),
+    /// The (first column of the) sample labels
+    Labels,
+}
+
+/// A DataSource made of train, validation and test partitions
+pub struct SplitSource {
+    pub train: InMemorySource,
+    pub validation: Option<InMemorySource>,
+    pub test: Option<InMemorySource>,
+}
+
+/// Partitions a finite source into train / validation / test sets
+///
+/// With `stratify` the ratio split is done per distinct value of an input
+/// column or of the labels so every partition keeps the label proportions.
+/// The train partition reshuffles every epoch, all shuffling is seeded by
+/// `seed`.
+pub fn split<T: DataSouce>(
+    source: &T,
+    strategy: SplitStrategy,
+    stratify: Option<Stratify>,
+    batch_size: u64,
+    seed: u64,
+) -> Result<SplitSource, HALError> {
+    let all = source.get_all().ok_or(HALError::UNKNOWN)?;
+    let num_samples = all.input.dims()[0];
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let (train, validation, test) = match strategy {
+        SplitStrategy::Indices {
+            train,
+            validation,
+            test,
+        } => {
+            let out_of_range = train
+                .iter()
+                .chain(validation.iter())
+                .chain(test.iter())
+                .any(|&i| i as u64 >= num_samples);
+            if out_of_range {
+                return Err(HALError::UNKNOWN);
+            }
+            (train, validation, test)
+        }
+        SplitStrategy::Ratio {
+            train: train_ratio,
+            validation: validation_ratio,
+        } => {
+            if train_ratio <= 0.0 || validation_ratio < 0.0 || train_ratio + validation_ratio > 1.0
+            {
+                return Err(HALError::UNKNOWN);
+            }
+
+            // group the samples by label so each group is split on its own
+            let mut groups: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+            match stratify {
+                Some(stratify) => {
+                    let labels = match (stratify, &all.labels) {
+                        (Stratify::Column(column), _) => column_values(&all.input, column)?,
+                        (Stratify::Labels, Some(labels)) => column_values(labels, 0)?,
+                        (Stratify::Labels, None) => return Err(HALError::UNKNOWN),
+                    };
+                    for (i, label) in labels.iter().enumerate() {
+                        groups.entry(label.to_bits()).or_default().push(i as u32);
+                    }
+                }
+                None => {
+                    groups.insert(0, (0..num_samples as u32).collect());
+                }
+            }
+
+            let (mut train, mut validation, mut test) = (Vec::new(), Vec::new(), Vec::new());
+            for (_, mut indices) in groups {
+                indices.shuffle(&mut rng);
+                let n = indices.len() as f32;
+                let num_train = (train_ratio * n).round() as usize;
+                let num_validation =
+                    ((validation_ratio * n).round() as usize).min(indices.len() - num_train);
+                train.extend_from_slice(&indices[..num_train]);
+                validation.extend_from_slice(&indices[num_train..num_train + num_validation]);
+                test.extend_from_slice(&indices[num_train + num_validation..]);
+            }
+            (train, validation, test)
+        }
+    };
+
+    let partition = |indices: &Vec<u32>, shuffle: bool, seed: u64| -> Option<InMemorySource> {
+        if indices.is_empty() {
+            return None;
+        }
+        let source = InMemorySource::new(
+            data::gather_rows(&all.input, indices),
+            data::gather_rows(&all.target, indices),
+            batch_size,
+            shuffle,
+            false,
+            seed,
+        );
+        Some(match all.labels {
+            Some(ref labels) => source.with_labels(data::gather_rows(labels, indices)),
+            None => source,
+        })
+    };
+
+    Ok(SplitSource {
+        train: partition(&train, true, seed).ok_or(HALError::UNKNOWN)?,
+        validation: partition(&validation, false, seed),
+        test: partition(&test, false, seed),
+    })
+}
+
+/// Returns the values of a column (dim 1) of a [samples, features] array
+fn column_values(array: &Array<f32>, column: usize) -> Result<Vec<f32>, HALError> {
+    let dims = array.dims();
+    if column as u64 >= dims[1] || dims[2] != 1 || dims[3] != 1 {
+        return Err(HALError::UNKNOWN);
+    }
+    let values = utils::array_to_vec(array);
+    let num_samples = dims[0] as usize;
+    Ok(values[column * num_samples..(column + 1) * num_samples].to_vec())
+}
+
+impl SplitSource {
+    /// The partition served by the test iterator
+    ///
+    /// Falls back to the validation, then to the train partition when the
+    /// split has no test samples (eg: a test ratio of 0).
+    fn evaluation_partition(&self) -> &InMemorySource {
+        self.test
+            .as_ref()
+            .or_else(|| self.validation.as_ref())
+            .unwrap_or(&self.train)
+    }
+}
+
+impl DataSouce for SplitSource {
+    fn info(&self) -> DataParams {
+        self.train.info()
+    }
+
+    fn get_train_iter(&self, num_batch: u64) -> Data {
+        self.train.get_train_iter(num_batch)
+    }
+
+    fn get_test_iter(&self, num_batch: u64) -> Data {
+        self.evaluation_partition().get_test_iter(num_batch)
+    }
+
+    fn num_batches(&self, batch_size: u64) -> u64 {
+        self.train.num_batches(batch_size)
+    }
+
+    fn get_validation_iter(&self, num_batch: u64) -> Option<Data> {
+        self.validation
+            .as_ref()
+            .map(|validation| validation.get_test_iter(num_batch))
+    }
+
+    fn num_validation_samples(&self) -> u64 {
+        self.validation
+            .as_ref()
+            .map_or(0, |validation| validation.info().num_samples)
+    }
+
+    fn num_test_samples(&self) -> u64 {
+        self.evaluation_partition().info().num_samples
+    }
+
+    fn get_all(&self) -> Option<Data> {
+        self.train.get_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use af::Dim4;
+
+    #[test]
+    fn stratified_ratio_split() {
+        // 8 samples, the second column holds the label (4 x 0, 4 x 1)
+        let values = (0..8)
+            .flat_map(|i| vec![i as f32, (i % 2) as f32])
+            .collect::<Vec<f32>>();
+        let input = data::row_major_to_array(&values, &[8, 2]);
+        let source = InMemorySource::autoencoder(input, 2, false, false, 0);
+
+        let strategy = SplitStrategy::Ratio {
+            train: 0.5,
+            validation: 0.25,
+        };
+        let splits = split(&source, strategy, Some(Stratify::Column(1)), 2, 7).unwrap();
+        assert_eq!(splits.info().num_samples, 4);
+        assert_eq!(splits.num_validation_samples(), 2);
+        assert_eq!(splits.num_test_samples(), 2);
+
+        // every partition keeps one label of each class per 2 samples
+        let validation = splits.validation.as_ref().unwrap().get_all().unwrap();
+        let labels = column_values(&validation.input, 1).unwrap();
+        assert_eq!(labels.iter().sum::<f32>(), 1.0);
+        assert_eq!(validation.input.dims(), Dim4::new(&[2, 2, 1, 1]));
+    }
+
+    #[test]
+    fn stratify_by_labels_without_test_partition() {
+        let input =
+            data::row_major_to_array(&(0..8).map(|i| i as f32).collect::<Vec<f32>>(), &[8, 1]);
+        let labels = data::row_major_to_array(&[0., 0., 0., 0., 1., 1., 1., 1.], &[8, 1]);
+        let source = InMemorySource::autoencoder(input, 2, false, false, 0).with_labels(labels);
+
+        let strategy = SplitStrategy::Ratio {
+            train: 0.5,
+            validation: 0.5,
+        };
+        let splits = split(&source, strategy, Some(Stratify::Labels), 2, 7).unwrap();
+        let validation = splits.validation.as_ref().unwrap().get_all().unwrap();
+        let labels = column_values(validation.labels.as_ref().unwrap(), 0).unwrap();
+        assert_eq!(labels.iter().sum::<f32>(), 2.0);
+
+        // the test iterator falls back to the validation partition
+        assert!(splits.test.is_none());
+        assert_eq!(splits.num_test_samples(), 4);
+        assert_eq!(
+            splits.get_test_iter(4).input.dims(),
+            Dim4::new(&[4, 1, 1, 1])
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use af::{Array, Dim4};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::f32::consts::PI;
+
+use crate::data::{Data, DataParams, DataSouce, InMemorySource};
+use crate::error::HALError;
+use crate::utils;
+
+/// The supported synthetic data generators
+///
+/// Wave generators sample `num_features` points of one period window per
+/// sample, the manifold generators need exactly 3 features.
+#[derive(Clone, Debug)]
+pub enum Generator {
+    /// Sum of sines, each sample draws a phase shift and amplitude scales
+    SineMixture {
+        frequencies: Vec<f32>,
+        amplitudes: Vec<f32>,
+        phase_noise: f32,
+        amplitude_noise: f32,
+        noise: f32,
+    },
+    /// +-1 square wave with a random phase shift per sample
+    Square {
+        frequency: f32,
+        phase_noise: f32,
+        noise: f32,
+    },
+    /// Rising sawtooth wave in [-1, 1] with a random phase shift per sample
+    Sawtooth {
+        frequency: f32,
+        phase_noise: f32,
+        noise: f32,
+    },
+    /// Isotropic gaussian clusters around centers drawn in [-spread, spread]
+    GaussianMixture {
+        num_clusters: usize,
+        spread: f32,
+        std: f32,
+    },
+    /// 2d swiss roll manifold embedded in 3d
+    SwissRoll { noise: f32 },
+    /// 2d S shaped manifold embedded in 3d
+    SCurve { noise: f32 },
+    /// Linear data of known intrinsic dimension `rank`
+    LowRank { rank: usize, noise: f32 },
+}
+
+/// Outliers injected into a synthetic dataset
+#[derive(Clone, Copy, Debug)]
+pub struct Anomalies {
+    /// Fraction of the samples turned into anomalies
+    pub fraction: f32,
+    /// Standard deviation of the gaussian noise added to every feature
+    pub magnitude: f32,
+}
+
+/// A finite synthetic DataSource with known latent structure
+///
+/// `latent` holds the ground truth factors of every sample ([samples, k]):
+/// the phase shift for waves, the cluster index for gaussian mixtures, the
+/// manifold coordinates for the swiss roll / S-curve and the factor scores
+/// for low rank data. The served labels are the anomaly flags (1 = anomaly)
+/// when anomalies are injected, the cluster index for gaussian mixtures and
+/// none otherwise. All randomness is seeded by `seed`.
+pub struct SyntheticSource {
+    pub data: InMemorySource,
+    pub latent: Array<f32>,
+    pub anomalies: Vec<u32>,
+}
+
+impl SyntheticSource {
+    pub fn new(
+        generator: Generator,
+        num_samples: u64,
+        num_features: u64,
+        anomalies: Option<Anomalies>,
+        batch_size: u64,
+        seed: u64,
+    ) -> Result<SyntheticSource, HALError> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (n, d) = (num_samples as usize, num_features as usize);
+        if n == 0 || d == 0 {
+            return Err(HALError::UNKNOWN);
+        }
+
+        // column major [samples, features] & [samples, latent] buffers
+        let (mut values, latent, num_latent) = match generator {
+            Generator::SineMixture {
+                ref frequencies,
+                ref amplitudes,
+                phase_noise,
+                amplitude_noise,
+                noise,
+            } => {
+                if frequencies.is_empty() || frequencies.len() != amplitudes.len() {
+                    return Err(HALError::UNKNOWN);
+                }
+                let mut values = vec![0.0f32; n * d];
+                let mut latent = vec![0.0f32; n];
+                for i in 0..n {
+                    let phase = phase_noise * utils::gaussian(&mut rng);
+                    latent[i] = phase;
+                    for (f, a) in frequencies.iter().zip(amplitudes.iter()) {
+                        let scale = a * (1.0 + amplitude_noise * utils::gaussian(&mut rng));
+                        for j in 0..d {
+                            values[i + n * j] += scale * (2.0 * PI * f * time(j, d) + phase).sin();
+                        }
+                    }
+                    add_noise(&mut values, i, n, d, noise, &mut rng);
+                }
+                (values, latent, 1)
+            }
+            Generator::Square {
+                frequency,
+                phase_noise,
+                noise,
+            }
+            | Generator::Sawtooth {
+                frequency,
+                phase_noise,
+                noise,
+            } => {
+                let square = matches!(generator, Generator::Square { .. });
+                let mut values = vec![0.0f32; n * d];
+                let mut latent = vec![0.0f32; n];
+                for i in 0..n {
+                    let phase = phase_noise * utils::gaussian(&mut rng);
+                    latent[i] = phase;
+                    for j in 0..d {
+                        let cycle = frequency * time(j, d) + phase / (2.0 * PI);
+                        let fraction = cycle - cycle.floor();
+                        values[i + n * j] = if square {
+                            if fraction < 0.5 {
+                                1.0
+                            } else {
+                                -1.0
+                            }
+                        } else {
+                            2.0 * fraction - 1.0
+                        };
+                    }
+                    add_noise(&mut values, i, n, d, noise, &mut rng);
+                }
+                (values, latent, 1)
+            }
+            Generator::GaussianMixture {
+                num_clusters,
+                spread,
+                std,
+            } => {
+                if num_clusters == 0 {
+                    return Err(HALError::UNKNOWN);
+                }
+                let centers = (0..num_clusters * d)
+                    .map(|_| rng.gen_range(-spread..=spread))
+                    .collect::<Vec<f32>>();
+                let mut values = vec![0.0f32; n * d];
+                let mut latent = vec![0.0f32; n];
+                for i in 0..n {
+                    let cluster = rng.gen_range(0..num_clusters);
+                    latent[i] = cluster as f32;
+                    for j in 0..d {
+                        values[i + n * j] =
+                            centers[cluster * d + j] + std * utils::gaussian(&mut rng);
+                    }
+                }
+                (values, latent, 1)
+            }
+            Generator::SwissRoll { noise } | Generator::SCurve { noise } => {
+                if d != 3 {
+                    return Err(HALError::UNKNOWN);
+                }
+                let swiss_roll = matches!(generator, Generator::SwissRoll { .. });
+                let mut values = vec![0.0f32; n * 3];
+                let mut latent = vec![0.0f32; n * 2];
+                for i in 0..n {
+                    let (u, v) = (rng.gen::<f32>(), rng.gen::<f32>());
+                    let point = if swiss_roll {
+                        let (t, h) = (1.5 * PI * (1.0 + 2.0 * u), 21.0 * v);
+                        latent[i] = t;
+                        latent[i + n] = h;
+                        [t * t.cos(), h, t * t.sin()]
+                    } else {
+                        let (t, h) = (3.0 * PI * (u - 0.5), 2.0 * v);
+                        latent[i] = t;
+                        latent[i + n] = h;
+                        [t.sin(), h, t.signum() * (t.cos() - 1.0)]
+                    };
+                    for (j, p) in point.iter().enumerate() {
+                        values[i + n * j] = *p;
+                    }
+                    add_noise(&mut values, i, n, 3, noise, &mut rng);
+                }
+                (values, latent, 2)
+            }
+            Generator::LowRank { rank, noise } => {
+                if rank == 0 || rank > d {
+                    return Err(HALError::UNKNOWN);
+                }
+                // x = z * W with z: [samples, rank] & W: [rank, features]
+                let basis = (0..rank * d)
+                    .map(|_| utils::gaussian(&mut rng))
+                    .collect::<Vec<f32>>();
+                let latent = (0..n * rank)
+                    .map(|_| utils::gaussian(&mut rng))
+                    .collect::<Vec<f32>>();
+                let mut values = vec![0.0f32; n * d];
+                for i in 0..n {
+                    for j in 0..d {
+                        values[i + n * j] = (0..rank)
+                            .map(|k| latent[i + n * k] * basis[k + rank * j])
+                            .sum();
+                    }
+                    add_noise(&mut values, i, n, d, noise, &mut rng);
+                }
+                (values, latent, rank)
+            }
+        };
+
+        // the anomaly flags take precedence over the generator labels
+        let mut labels = match generator {
+            Generator::GaussianMixture { .. } => Some(latent.clone()),
+            _ => None,
+        };
+        let mut anomalous = Vec::new();
+        if let Some(anomalies) = anomalies {
+            let count = ((anomalies.fraction.max(0.0).min(1.0) * n as f32).round()) as usize;
+            let mut order = (0..n as u32).collect::<Vec<u32>>();
+            order.shuffle(&mut rng);
+            anomalous = order[..count].to_vec();
+            anomalous.sort_unstable();
+
+            let mut flags = vec![0.0f32; n];
+            for &i in anomalous.iter() {
+                flags[i as usize] = 1.0;
+                add_noise(&mut values, i as usize, n, d, anomalies.magnitude, &mut rng);
+            }
+            labels = Some(flags);
+        }
+
+        let input = utils::vec_to_array(values, Dim4::new(&[num_samples, num_features, 1, 1]));
+        let mut data = InMemorySource::autoencoder(input, batch_size, true, false, seed);
+        if let Some(labels) = labels {
+            data = data.with_labels(utils::vec_to_array(
+                labels,
+                Dim4::new(&[num_samples, 1, 1, 1]),
+            ));
+        }
+
+        Ok(SyntheticSource {
+            data,
+            latent: utils::vec_to_array(latent, Dim4::new(&[num_samples, num_latent as u64, 1, 1])),
+            anomalies: anomalous,
+        })
+    }
+}
+
+/// Position of feature j in the unit window
+fn time(j: usize, d: usize) -> f32 {
+    j as f32 / d as f32
+}
+
+/// Adds gaussian noise to every feature of sample i of a [n, d] buffer
+fn add_noise(values: &mut [f32], i: usize, n: usize, d: usize, std: f32, rng: &mut StdRng) {
+    if std == 0.0 {
+        return;
+    }
+    for j in 0..d {
+        values[i + n * j] += std * utils::gaussian(rng);
+    }
+}
+
+impl DataSouce for SyntheticSource {
+    fn info(&self) -> DataParams {
+        self.data.info()
+    }
+
+    fn get_train_iter(&self, num_batch: u64) -> Data {
+        self.data.get_train_iter(num_batch)
+    }
+
+    fn get_test_iter(&self, num_batch: u64) -> Data {
+        self.data.get_test_iter(num_batch)
+    }
+
+    fn num_batches(&self, batch_size: u64) -> u64 {
+        self.data.num_batches(batch_size)
+    }
+
+    fn get_all(&self) -> Option<Data> {
+        self.data.get_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn low_rank_with_anomalies() {
+        let generator = Generator::LowRank {
+            rank: 2,
+            noise: 0.0,
+        };
+        let anomalies = Anomalies {
+            fraction: 0.1,
+            magnitude: 5.0,
+        };
+        let source = SyntheticSource::new(generator, 20, 6, Some(anomalies), 4, 3).unwrap();
+        assert_eq!(source.anomalies.len(), 2);
+        assert_eq!(source.latent.dims(), Dim4::new(&[20, 2, 1, 1]));
+
+        let all = source.get_all().unwrap();
+        let labels = utils::array_to_vec(&all.labels.unwrap());
+        assert_eq!(labels.iter().sum::<f32>(), 2.0);
+        for &i in source.anomalies.iter() {
+            assert_eq!(labels[i as usize], 1.0);
+        }
+    }
+
+    #[test]
+    fn manifolds_need_three_features() {
+        let generator = Generator::SwissRoll { noise: 0.0 };
+        assert!(SyntheticSource::new(generator.clone(), 8, 2, None, 4, 0).is_err());
+        let source = SyntheticSource::new(generator, 8, 3, None, 4, 0).unwrap();
+        assert_eq!(source.info().num_samples, 8);
+    }
+}
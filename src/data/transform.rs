@@ -0,0 +1,260 @@
+use af::{Array, Dim4};
+use serde::{Deserialize, Serialize};
+
+use crate::data::{Data, DataParams, DataSouce};
+use crate::error::HALError;
+use crate::utils;
+
+/// The supported feature transforms
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TransformKind {
+    /// (x - min) / (max - min)
+    MinMax,
+    /// (x - mean) / std
+    Standard,
+    /// (x - median) / (q75 - q25)
+    Robust,
+    /// ln(x - min + 1), values below the training min are clamped to it
+    Log,
+}
+
+/// Helper to provide a transform kind from a string
+pub fn get_transform_kind(name: &str) -> Result<TransformKind, HALError> {
+    match name {
+        "minmax" => Ok(TransformKind::MinMax),
+        "standard" => Ok(TransformKind::Standard),
+        "robust" => Ok(TransformKind::Robust),
+        "log" => Ok(TransformKind::Log),
+        _ => Err(HALError::UNKNOWN),
+    }
+}
+
+/// A per-feature transform fitted on training data
+///
+/// Features are every element of a sample, ie: dims 1..3 of a [samples, ...]
+/// Array. Scaling transforms compute (x - offset) / scale, the log transform
+/// computes ln(max(x - offset, 1)) so unseen values below the training min
+/// map to 0 instead of NaN.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Transform {
+    pub kind: TransformKind,
+    pub offset: Vec<f32>,
+    pub scale: Vec<f32>,
+}
+
+impl Transform {
+    /// Fits the per-feature statistics of the given [samples, ...] Array
+    pub fn fit(kind: TransformKind, values: &Array<f32>) -> Transform {
+        let num_samples = values.dims()[0] as usize;
+        let host = utils::array_to_vec(values);
+
+        let (offset, scale): (Vec<f32>, Vec<f32>) = host
+            .chunks(num_samples)
+            .map(|feature| {
+                let (offset, scale) = match kind {
+                    TransformKind::MinMax => {
+                        let min = feature.iter().cloned().fold(f32::INFINITY, f32::min);
+                        let max = feature.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                        (min, max - min)
+                    }
+                    TransformKind::Standard => {
+                        let n = feature.len() as f32;
+                        let mean = feature.iter().sum::<f32>() / n;
+                        let var = feature.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / n;
+                        (mean, var.sqrt())
+                    }
+                    TransformKind::Robust => {
+                        let mut sorted = feature.to_vec();
+                        sorted.sort_by(|a, b| a.total_cmp(b));
+                        let iqr = percentile(&sorted, 75.0) - percentile(&sorted, 25.0);
+                        (percentile(&sorted, 50.0), iqr)
+                    }
+                    TransformKind::Log => {
+                        let min = feature.iter().cloned().fold(f32::INFINITY, f32::min);
+                        (min - 1.0, 1.0)
+                    }
+                };
+                // constant features are only shifted
+                (offset, if scale > 0.0 { scale } else { 1.0 })
+            })
+            .unzip();
+
+        Transform {
+            kind,
+            offset,
+            scale,
+        }
+    }
+
+    /// Returns the statistics as [1, features] Arrays
+    fn stats(&self) -> (Array<f32>, Array<f32>) {
+        let dims = Dim4::new(&[1, self.offset.len() as u64, 1, 1]);
+        (
+            utils::raw_to_array(&self.offset, dims),
+            utils::raw_to_array(&self.scale, dims),
+        )
+    }
+
+    /// Views a [samples, ...] Array as [samples, features]
+    fn flatten(&self, values: &Array<f32>) -> Array<f32> {
+        let dims = values.dims();
+        let num_features = dims[1] * dims[2] * dims[3];
+        assert!(
+            num_features == self.offset.len() as u64,
+            "transform was fitted on {} features, got {}",
+            self.offset.len(),
+            num_features
+        );
+        af::moddims(values, Dim4::new(&[dims[0], num_features, 1, 1]))
+    }
+
+    pub fn transform(&self, values: &Array<f32>) -> Array<f32> {
+        let (offset, scale) = self.stats();
+        let shifted = af::sub(&self.flatten(values), &offset, true);
+        let transformed = match self.kind {
+            TransformKind::Log => af::log(&af::maxof(&shifted, &1.0f32, true)),
+            _ => af::div(&shifted, &scale, true),
+        };
+        af::moddims(&transformed, values.dims())
+    }
+
+    /// Maps transformed values (eg: reconstructions) back to the original units
+    pub fn inverse_transform(&self, values: &Array<f32>) -> Array<f32> {
+        let (offset, scale) = self.stats();
+        let flat = self.flatten(values);
+        let unscaled = match self.kind {
+            TransformKind::Log => af::exp(&flat),
+            _ => af::mul(&flat, &scale, true),
+        };
+        af::moddims(&af::add(&unscaled, &offset, true), values.dims())
+    }
+}
+
+/// Linearly interpolated percentile (0 - 100) of sorted values
+pub(crate) fn percentile(sorted: &[f32], p: f32) -> f32 {
+    let rank = (p / 100.0) * (sorted.len() - 1) as f32;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f32)
+}
+
+/// Wraps a DataSource and applies fitted transforms to its inputs and targets
+pub struct TransformedSource<T: DataSouce> {
+    pub source: T,
+    pub input_transform: Transform,
+    pub target_transform: Transform,
+}
+
+impl<T: DataSouce> TransformedSource<T> {
+    /// Attaches already fitted transforms (eg: loaded with a model checkpoint)
+    pub fn new(
+        source: T,
+        input_transform: Transform,
+        target_transform: Transform,
+    ) -> TransformedSource<T> {
+        TransformedSource {
+            source,
+            input_transform,
+            target_transform,
+        }
+    }
+
+    /// Fits the transforms on the training data of a finite source
+    pub fn fit(source: T, kind: TransformKind) -> Result<TransformedSource<T>, HALError> {
+        let all = source.get_all().ok_or(HALError::UNKNOWN)?;
+        let input_transform = Transform::fit(kind, &all.input);
+        let target_transform = Transform::fit(kind, &all.target);
+        Ok(TransformedSource::new(
+            source,
+            input_transform,
+            target_transform,
+        ))
+    }
+
+    fn apply(&self, batch: Data) -> Data {
+        Data {
+            input: self.input_transform.transform(&batch.input),
+            target: self.target_transform.transform(&batch.target),
+            labels: batch.labels,
+        }
+    }
+}
+
+impl<T: DataSouce> DataSouce for TransformedSource<T> {
+    fn info(&self) -> DataParams {
+        self.source.info()
+    }
+
+    fn get_train_iter(&self, num_batch: u64) -> Data {
+        self.apply(self.source.get_train_iter(num_batch))
+    }
+
+    fn get_test_iter(&self, num_batch: u64) -> Data {
+        self.apply(self.source.get_test_iter(num_batch))
+    }
+
+    fn num_batches(&self, batch_size: u64) -> u64 {
+        self.source.num_batches(batch_size)
+    }
+
+    fn get_validation_iter(&self, num_batch: u64) -> Option<Data> {
+        self.source
+            .get_validation_iter(num_batch)
+            .map(|batch| self.apply(batch))
+    }
+
+    fn num_validation_samples(&self) -> u64 {
+        self.source.num_validation_samples()
+    }
+
+    fn num_test_samples(&self) -> u64 {
+        self.source.num_test_samples()
+    }
+
+    fn get_all(&self) -> Option<Data> {
+        self.source.get_all().map(|all| self.apply(all))
+    }
+
+    fn shutdown(&self) {
+        self.source.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse_transform_round_trip() {
+        let values = utils::vec_to_array(
+            vec![1.0, 2.0, 3.0, 10.0, 20.0, 40.0],
+            Dim4::new(&[3, 2, 1, 1]),
+        );
+        for kind in [
+            TransformKind::MinMax,
+            TransformKind::Standard,
+            TransformKind::Robust,
+            TransformKind::Log,
+        ]
+        .iter()
+        {
+            let transform = Transform::fit(*kind, &values);
+            let restored = transform.inverse_transform(&transform.transform(&values));
+            let error = utils::array_to_vec(&restored)
+                .iter()
+                .zip(utils::array_to_vec(&values).iter())
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0f32, f32::max);
+            assert!(error < 1e-3, "{:?} round trip error {}", kind, error);
+        }
+    }
+
+    #[test]
+    fn log_clamps_values_below_the_training_min() {
+        let train = utils::vec_to_array(vec![1.0, 2.0, 3.0], Dim4::new(&[3, 1, 1, 1]));
+        let transform = Transform::fit(TransformKind::Log, &train);
+
+        let unseen = utils::vec_to_array(vec![-5.0, 0.5], Dim4::new(&[2, 1, 1, 1]));
+        let transformed = utils::array_to_vec(&transform.transform(&unseen));
+        assert_eq!(transformed, vec![0.0, 0.0]);
+    }
+}
@@ -0,0 +1,187 @@
+use af::{Array, Dim4};
+
+use crate::data::csv::{read_csv, resolve_columns, select_columns};
+use crate::data::{CsvParams, Data, DataParams, DataSouce, InMemorySource};
+use crate::error::HALError;
+use crate::utils;
+
+/// What a window is trained to produce
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowMode {
+    /// The target is the input window itself
+    Reconstruction,
+    /// The target is the window that directly follows the input window
+    Forecast,
+}
+
+/// Options used to cut a series into windows
+#[derive(Clone, Copy, Debug)]
+pub struct WindowParams {
+    pub window: u64,
+    pub stride: u64,
+    pub mode: WindowMode,
+}
+
+/// A DataSource cutting a long series into overlapping windows
+///
+/// The series is a [time, features] Array (a 1-D series is [time, 1]). Windows
+/// of `window` steps start every `stride` steps and are served as
+/// [batch, features, window] Arrays, ie: time along dim 2 as sliced by
+/// `Sequential::backward`. Windows are materialized once and served by an
+/// `InMemorySource` (optionally reshuffled every epoch with `seed`).
+pub struct WindowSource {
+    pub data: InMemorySource,
+    pub window_params: WindowParams,
+}
+
+impl WindowSource {
+    pub fn new(
+        series: &Array<f32>,
+        window_params: WindowParams,
+        batch_size: u64,
+        shuffle: bool,
+        seed: u64,
+    ) -> Result<WindowSource, HALError> {
+        let WindowParams {
+            window,
+            stride,
+            mode,
+        } = window_params;
+        let dims = series.dims();
+        if dims[2] != 1 || dims[3] != 1 || window == 0 || stride == 0 {
+            return Err(HALError::UNKNOWN);
+        }
+        let (length, num_features) = (dims[0], dims[1]);
+
+        // a forecast needs room for the following window as well
+        let span = match mode {
+            WindowMode::Reconstruction => window,
+            WindowMode::Forecast => 2 * window,
+        };
+        if length < span {
+            return Err(HALError::UNKNOWN);
+        }
+        let num_windows = (length - span) / stride + 1;
+
+        let values = utils::array_to_vec(series);
+        let cut = |offset: u64| -> Array<f32> {
+            let (n, f, w) = (num_windows as usize, num_features as usize, window as usize);
+            let mut windows = vec![0.0f32; n * f * w];
+            for b in 0..n {
+                let start = offset as usize + b * stride as usize;
+                for t in 0..w {
+                    for j in 0..f {
+                        windows[b + n * (j + f * t)] = values[start + t + length as usize * j];
+                    }
+                }
+            }
+            utils::vec_to_array(windows, Dim4::new(&[num_windows, num_features, window, 1]))
+        };
+
+        let input = cut(0);
+        let target = match mode {
+            WindowMode::Reconstruction => input.copy(),
+            WindowMode::Forecast => cut(window),
+        };
+
+        Ok(WindowSource {
+            data: InMemorySource::new(input, target, batch_size, shuffle, false, seed),
+            window_params,
+        })
+    }
+
+    /// Windows the selected input columns of a CSV/TSV table (rows are time steps)
+    pub fn from_csv(
+        path: &str,
+        csv_params: CsvParams,
+        window_params: WindowParams,
+        batch_size: u64,
+        shuffle: bool,
+        seed: u64,
+    ) -> Result<WindowSource, HALError> {
+        let (header, rows) = read_csv(path, csv_params.delimiter, csv_params.has_header)?;
+        if rows.is_empty() {
+            return Err(HALError::PARSE_ERROR);
+        }
+        let columns = resolve_columns(&header, csv_params.input_columns.as_ref())?;
+        let series = select_columns(&rows, &columns);
+        WindowSource::new(&series, window_params, batch_size, shuffle, seed)
+    }
+}
+
+impl DataSouce for WindowSource {
+    fn info(&self) -> DataParams {
+        self.data.info()
+    }
+
+    fn get_train_iter(&self, num_batch: u64) -> Data {
+        self.data.get_train_iter(num_batch)
+    }
+
+    fn get_test_iter(&self, num_batch: u64) -> Data {
+        self.data.get_test_iter(num_batch)
+    }
+
+    fn num_batches(&self, batch_size: u64) -> u64 {
+        self.data.num_batches(batch_size)
+    }
+
+    fn get_all(&self) -> Option<Data> {
+        self.data.get_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Model;
+    use crate::test_utils::dense_model;
+
+    #[test]
+    fn forecast_windows() {
+        // 2 features over 7 steps: f0 = t, f1 = 10 * t
+        let values = (0..7)
+            .map(|t| t as f32)
+            .chain((0..7).map(|t| 10.0 * t as f32))
+            .collect::<Vec<f32>>();
+        let series = utils::vec_to_array(values, Dim4::new(&[7, 2, 1, 1]));
+        let window_params = WindowParams {
+            window: 2,
+            stride: 1,
+            mode: WindowMode::Forecast,
+        };
+        let source = WindowSource::new(&series, window_params, 4, false, 0).unwrap();
+        assert_eq!(source.info().num_samples, 4);
+
+        let batch = source.get_test_iter(4);
+        assert_eq!(batch.input.dims(), Dim4::new(&[4, 2, 2, 1]));
+        let input = utils::array_to_vec(&batch.input);
+        let target = utils::array_to_vec(&batch.target);
+        // second window, second feature, second time step
+        assert_eq!(input[1 + 4 * 3], 20.0);
+        // its forecast starts right after the window
+        assert_eq!(target[1 + 4], 30.0);
+    }
+
+    #[test]
+    fn fit_unrolls_every_window_step() {
+        let values = (0..20)
+            .map(|t| (t as f32 / 4.0).sin())
+            .collect::<Vec<f32>>();
+        let series = utils::vec_to_array(values, Dim4::new(&[20, 1, 1, 1]));
+        let window_params = WindowParams {
+            window: 3,
+            stride: 1,
+            mode: WindowMode::Reconstruction,
+        };
+        let source = WindowSource::new(&series, window_params, 6, true, 0).unwrap();
+
+        let mut model = dense_model(&[(1, 4), (4, 1)], "tanh");
+        let history = model.fit(&source, 2, 6, None, false);
+        assert_eq!(
+            history.iteration_losses.len() as u64,
+            2 * source.num_batches(6)
+        );
+        assert!(history.iteration_losses.iter().all(|loss| loss.is_finite()));
+    }
+}
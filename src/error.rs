@@ -12,4 +12,12 @@ pub enum HALError {
     /// Unknown error
     ///
     UNKNOWN = 2,
+    ///
+    /// File could not be opened, read or written
+    ///
+    IO_ERROR = 3,
+    ///
+    /// File contents could not be parsed
+    ///
+    PARSE_ERROR = 4,
 }
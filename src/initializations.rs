@@ -1,12 +1,33 @@
+use std::cell::RefCell;
+
 use crate::{error::HALError, utils};
 use af::{Array, Dim4, HasAfEnum};
 use arrayfire::FloatingPoint;
-use rand::{self, Rng};
+use rand::rngs::StdRng;
+use rand::{self, Rng, SeedableRng};
+
+thread_local! {
+    static SEEDED_RNG: RefCell<Option<StdRng>> = RefCell::new(None);
+}
+
+/// Makes the random initializations (and input corruptions) of the current thread reproducible
+///
+/// With `None` everything is seeded randomly again (the default).
+pub fn set_seed(seed: Option<u64>) {
+    SEEDED_RNG.with(|rng| *rng.borrow_mut() = seed.map(StdRng::seed_from_u64));
+}
+
+/// Draws the next seed of the current thread, random unless `set_seed` was called
+pub fn next_seed() -> u64 {
+    SEEDED_RNG.with(|rng| match rng.borrow_mut().as_mut() {
+        Some(rng) => rng.gen::<u64>(),
+        None => rand::thread_rng().gen::<u64>(),
+    })
+}
 
 /// A helper to return a normal shape
 pub fn normal<T: HasAfEnum + FloatingPoint>(dims: Dim4) -> Array<T> {
-    let mut rng = rand::thread_rng();
-    af::set_seed(rng.gen::<u64>());
+    af::set_seed(next_seed());
 
     let src_type = T::get_af_dtype();
     let u = af::randn::<T>(dims);
@@ -22,8 +43,7 @@ pub fn normal<T: HasAfEnum + FloatingPoint>(dims: Dim4) -> Array<T> {
 
 /// A helper to provide a uniform shape
 pub fn uniform<T: HasAfEnum + FloatingPoint>(dims: Dim4) -> Array<T> {
-    let mut rng = rand::thread_rng();
-    af::set_seed(rng.gen::<u64>());
+    af::set_seed(next_seed());
 
     let src_type = T::get_af_dtype();
     let u = af::randu::<T>(dims);
@@ -47,6 +67,9 @@ pub fn ones(dims: Dim4) -> Array<f32> {
     utils::constant(dims, 1.0f32)
 }
 
+/// Names of the initializations provided by `get_initialization`
+pub const INITIALIZATIONS: [&str; 4] = ["normal", "uniform", "zeros", "ones"];
+
 pub fn get_initialization(name: &str, dims: Dim4) -> Result<Array<f32>, HALError> {
     match name {
         "normal" => Ok(normal(dims)),
@@ -0,0 +1,191 @@
+use af::{Array, Dim4, MatProp};
+
+use crate::data::DataSouce;
+use crate::error::HALError;
+use crate::model::Sequential;
+use crate::utils;
+
+/// Latent codes of a whole dataset with the labels of its samples (if any)
+pub struct Latent {
+    /// [samples, features] codes, time slices are flattened into the features
+    pub codes: Array<f32>,
+    pub labels: Option<Array<f32>>,
+}
+
+/// Collects the outputs of `layer` (a "name" given to `add` or an index) for
+/// one pass of the test iterator of `source`
+pub fn extract<T: DataSouce>(
+    model: &Sequential,
+    source: &T,
+    layer: &str,
+    batch_size: u64,
+) -> Result<Latent, HALError> {
+    let last_layer = model.layer_index(layer).ok_or(HALError::UNKNOWN)?;
+    let mut codes: Option<Array<f32>> = None;
+    let mut labels: Option<Option<Array<f32>>> = None;
+
+    let complete = model.for_each_encoded(
+        source.num_test_samples(),
+        batch_size,
+        last_layer,
+        |n| Some(source.get_test_iter(n)),
+        |batch, slices| {
+            let chunk = flatten(
+                &slices[1..].iter().fold(slices[0].clone(), |joined, slice| {
+                    af::join(2, &joined, slice)
+                }),
+            );
+            codes = Some(match codes.take() {
+                Some(previous) => af::join(0, &previous, &chunk),
+                None => chunk,
+            });
+            // labels are only kept when every batch has them
+            labels = Some(match (labels.take(), &batch.labels) {
+                (None, Some(batch_labels)) => Some(batch_labels.copy()),
+                (Some(Some(previous)), Some(batch_labels)) => {
+                    Some(af::join(0, &previous, batch_labels))
+                }
+                _ => None,
+            });
+        },
+    );
+    if !complete {
+        return Err(HALError::UNKNOWN);
+    }
+
+    Ok(Latent {
+        codes: codes.ok_or(HALError::UNKNOWN)?,
+        labels: labels.unwrap_or(None),
+    })
+}
+
+/// Views a [samples, ...] Array as [samples, features]
+fn flatten(values: &Array<f32>) -> Array<f32> {
+    let dims = values.dims();
+    af::moddims(
+        values,
+        Dim4::new(&[dims[0], dims[1] * dims[2] * dims[3], 1, 1]),
+    )
+}
+
+impl Latent {
+    /// Projects the codes onto their first `num_components` principal components
+    ///
+    /// Returns the projected codes (with the same labels) and the fraction of
+    /// the variance explained by each kept component.
+    pub fn pca(&self, num_components: usize) -> Result<(Latent, Vec<f32>), HALError> {
+        let dims = self.codes.dims();
+        let (num_samples, num_features) = (dims[0], dims[1]);
+        if num_components == 0 || num_components as u64 > num_features.min(num_samples) {
+            return Err(HALError::UNKNOWN);
+        }
+
+        let mean = af::mean(&self.codes, 0);
+        let centered = af::sub(&self.codes, &mean, true);
+        let (_, s, vt) = af::svd(&centered);
+
+        // columns of V are the principal directions, sorted by singular value
+        let projected = af::matmul(&centered, &vt, MatProp::NONE, MatProp::TRANS);
+        let values = utils::array_to_vec(&projected);
+        let kept = values[..num_samples as usize * num_components].to_vec();
+
+        let variances = utils::array_to_vec(&s)
+            .iter()
+            .map(|v| v * v)
+            .collect::<Vec<f32>>();
+        let total = variances.iter().sum::<f32>().max(1e-12);
+        let ratios = variances[..num_components]
+            .iter()
+            .map(|v| v / total)
+            .collect();
+
+        Ok((
+            Latent {
+                codes: utils::vec_to_array(
+                    kept,
+                    Dim4::new(&[num_samples, num_components as u64, 1, 1]),
+                ),
+                labels: self.labels.as_ref().map(|labels| labels.copy()),
+            },
+            ratios,
+        ))
+    }
+
+    /// Writes one row per sample: the codes z0..zN followed by the label columns
+    pub fn save_csv(&self, path: &str) -> Result<(), HALError> {
+        let num_samples = self.codes.dims()[0] as usize;
+        let codes = utils::array_to_vec(&self.codes);
+        let labels = self
+            .labels
+            .as_ref()
+            .map(|labels| utils::array_to_vec(&flatten(labels)));
+        let num_codes = codes.len() / num_samples;
+        let num_labels = labels.as_ref().map_or(0, |l| l.len() / num_samples);
+
+        let mut writer = csv::Writer::from_path(path).map_err(|_| HALError::IO_ERROR)?;
+        let mut header = (0..num_codes)
+            .map(|j| format!("z{}", j))
+            .collect::<Vec<String>>();
+        header.extend((0..num_labels).map(|j| match num_labels {
+            1 => "label".to_string(),
+            _ => format!("label{}", j),
+        }));
+        writer
+            .write_record(&header)
+            .map_err(|_| HALError::IO_ERROR)?;
+
+        for i in 0..num_samples {
+            let mut row = (0..num_codes)
+                .map(|j| codes[i + num_samples * j].to_string())
+                .collect::<Vec<String>>();
+            if let Some(labels) = &labels {
+                row.extend((0..num_labels).map(|j| labels[i + num_samples * j].to_string()));
+            }
+            writer.write_record(&row).map_err(|_| HALError::IO_ERROR)?;
+        }
+        writer.flush().map_err(|_| HALError::IO_ERROR)
+    }
+
+    /// Writes the codes (and optionally the labels) as .npy arrays
+    pub fn save_npy(&self, path: &str, labels_path: Option<&str>) -> Result<(), HALError> {
+        utils::write_npy(&self.codes, path)?;
+        match (labels_path, &self.labels) {
+            (Some(labels_path), Some(labels)) => utils::write_npy(labels, labels_path),
+            (Some(_), None) => Err(HALError::UNKNOWN),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Generator, SyntheticSource};
+    use crate::model::Model;
+    use crate::test_utils::{dense_params, sgd_model};
+
+    #[test]
+    fn extract_named_bottleneck() {
+        let mut model = sgd_model();
+        for (name, input_size, output_size) in [("encoder", 6, 3), ("decoder", 3, 6)].iter() {
+            let mut params = dense_params(*input_size, *output_size, "tanh");
+            params.insert("name", name.to_string());
+            model.add("dense", params);
+        }
+
+        let generator = Generator::GaussianMixture {
+            num_clusters: 2,
+            spread: 1.0,
+            std: 0.1,
+        };
+        let source = SyntheticSource::new(generator, 10, 6, None, 4, 0).unwrap();
+        let latent = extract(&model, &source, "encoder", 4).unwrap();
+        assert_eq!(latent.codes.dims(), Dim4::new(&[10, 3, 1, 1]));
+        assert_eq!(latent.labels.as_ref().unwrap().dims()[0], 10);
+
+        let (projected, ratios) = latent.pca(2).unwrap();
+        assert_eq!(projected.codes.dims(), Dim4::new(&[10, 2, 1, 1]));
+        assert!(ratios[0] >= ratios[1]);
+        assert!(extract(&model, &source, "missing", 4).is_err());
+    }
+}
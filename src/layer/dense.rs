@@ -18,7 +18,7 @@ impl Layer for Dense {
         // denseの1forward分は1層分しかないので先頭分のみを取得?
         let a_t = layer::linear(
             inputs,
-            &ltex.weights[0],
+            &ltex.effective_weight(),
             Some(&ltex.biases[0]),
             &ltex.activations[0],
         );
@@ -42,19 +42,46 @@ impl Layer for Dense {
 
     fn backward(&self, params: Arc<Mutex<Params>>, delta: &Array<f32>) -> Array<f32> {
         let mut ltex = params.lock().unwrap();
+        // slices are unrolled forward and walked back in reverse
+        let t = ltex.current_unroll - 1;
         // utilize the helper to get our deltas
         let (delta_t, dw, db) = layer::linear_backward(
             delta,
-            &ltex.inputs[0],
-            &ltex.outputs[0],
+            &ltex.inputs[t],
+            &ltex.outputs[t],
             &ltex.activations[0],
         );
 
-        ltex.deltas[0] = af::add(&ltex.deltas[0], &dw, false);
-        ltex.deltas[1] = af::add(&ltex.deltas[1], &db, false);
+        match ltex.tied_weights.clone() {
+            Some(source) => {
+                // accumulate into the shared weight, only the bias is ours
+                let mut stex = source.lock().unwrap();
+                stex.deltas[0] = af::add(&stex.deltas[0], &af::transpose(&dw, false), false);
+                ltex.deltas[0] = af::add(&ltex.deltas[0], &db, false);
+            }
+            None => {
+                ltex.deltas[0] = af::add(&ltex.deltas[0], &dw, false);
+                ltex.deltas[1] = af::add(&ltex.deltas[1], &db, false);
+            }
+        }
 
         ltex.current_unroll -= 1;
 
-        af::matmul(&delta_t, &ltex.weights[0], MatProp::NONE, MatProp::TRANS)
+        af::matmul(
+            &delta_t,
+            &ltex.effective_weight(),
+            MatProp::NONE,
+            MatProp::TRANS,
+        )
+    }
+
+    fn predict(&self, params: Arc<Mutex<Params>>, inputs: &Array<f32>) -> Array<f32> {
+        let ltex = params.lock().unwrap();
+        layer::linear(
+            inputs,
+            &ltex.effective_weight(),
+            Some(&ltex.biases[0]),
+            &ltex.activations[0],
+        )
     }
 }
@@ -9,6 +9,9 @@ use arrayfire::{Array, MatProp};
 pub trait Layer {
     fn forward(&self, params: Arc<Mutex<Params>>, inputs: &Array<f32>) -> Array<f32>;
     fn backward(&self, params: Arc<Mutex<Params>>, delta: &Array<f32>) -> Array<f32>;
+
+    /// Inference only forward pass, nothing is cached for backprop
+    fn predict(&self, params: Arc<Mutex<Params>>, inputs: &Array<f32>) -> Array<f32>;
 }
 
 /// Helper to run f(wx + b) where bias is optional
@@ -1,16 +1,26 @@
 extern crate arrayfire as af;
 pub mod activations;
+pub mod anomaly;
+pub mod callbacks;
+pub mod config;
+pub mod constraints;
 pub mod data;
 pub mod error;
 pub mod initializations;
+pub mod latent;
 pub mod layer;
 pub mod loss;
+pub mod metrics;
 pub mod model;
 pub mod optimizer;
 pub mod params;
 pub mod plot;
+pub mod regularizers;
 pub mod utils;
 
+#[cfg(test)]
+mod test_utils;
+
 // #[cfg(test)]
 // mod tests {
 //     use super::*;
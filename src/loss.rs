@@ -15,6 +15,17 @@ pub fn mse(pred: &Array<f32>, target: &Array<f32>) -> f32 {
     0.5f32 * af::mean_all(&l2_vec(pred, target)).0 as f32
 }
 
+/// Provides the per-sample mean squared error [batch, 1]
+pub fn mse_per_sample(pred: &Array<f32>, target: &Array<f32>) -> Array<f32> {
+    let dims = pred.dims();
+    let num_features = dims[1] * dims[2] * dims[3];
+    let flat = af::moddims(
+        &l2_vec(pred, target),
+        af::Dim4::new(&[dims[0], num_features, 1, 1]),
+    );
+    af::mul(&0.5f32, &af::mean(&flat, 1), false)
+}
+
 /// Provides the vector derivative of the mean squared error
 pub fn mse_derivative(pred: &Array<f32>, target: &Array<f32>) -> Array<f32> {
     af::sub(pred, target, false)
@@ -28,6 +39,18 @@ pub fn get_loss(name: &str, pred: &Array<f32>, target: &Array<f32>) -> Result<f3
     }
 }
 
+/// Helper to provide a per-sample loss vector from a string
+pub fn get_loss_per_sample(
+    name: &str,
+    pred: &Array<f32>,
+    target: &Array<f32>,
+) -> Result<Array<f32>, HALError> {
+    match name {
+        "mse" => Ok(mse_per_sample(pred, target)),
+        _ => Err(HALError::UNKNOWN),
+    }
+}
+
 /// Helper to provide a loss derivative from a string
 pub fn get_loss_derivative(
     name: &str,
@@ -0,0 +1,283 @@
+use af::Array;
+
+use crate::error::HALError;
+use crate::utils;
+
+/// Names of the metrics provided by `get_metric`
+pub const METRICS: [&str; 11] = [
+    "mae",
+    "rmse",
+    "r2",
+    "explained_variance",
+    "cosine",
+    "psnr",
+    "ssim",
+    "accuracy",
+    "precision",
+    "recall",
+    "f1",
+];
+
+const EPSILON: f32 = 1e-12;
+
+/// Mean absolute error
+pub fn mae(pred: &Array<f32>, target: &Array<f32>) -> f32 {
+    af::mean_all(&af::abs(&af::sub(pred, target, false))).0 as f32
+}
+
+/// Root mean squared error
+pub fn rmse(pred: &Array<f32>, target: &Array<f32>) -> f32 {
+    let diff = af::sub(pred, target, false);
+    (af::mean_all(&af::mul(&diff, &diff, false)).0 as f32).sqrt()
+}
+
+/// Coefficient of determination over all the elements, 1 - SS_res / SS_tot
+pub fn r2(pred: &Array<f32>, target: &Array<f32>) -> f32 {
+    let (p, t) = (utils::array_to_vec(pred), utils::array_to_vec(target));
+    let mean = mean(&t);
+    let ss_res: f32 = p.iter().zip(t.iter()).map(|(p, t)| (t - p) * (t - p)).sum();
+    let ss_tot: f32 = t.iter().map(|t| (t - mean) * (t - mean)).sum();
+    1.0 - ss_res / ss_tot.max(EPSILON)
+}
+
+/// 1 - Var(target - pred) / Var(target) over all the elements
+pub fn explained_variance(pred: &Array<f32>, target: &Array<f32>) -> f32 {
+    let (p, t) = (utils::array_to_vec(pred), utils::array_to_vec(target));
+    let residuals = p
+        .iter()
+        .zip(t.iter())
+        .map(|(p, t)| t - p)
+        .collect::<Vec<f32>>();
+    1.0 - variance(&residuals) / variance(&t).max(EPSILON)
+}
+
+/// Mean cosine similarity between the predicted and target samples (dim 0)
+pub fn cosine(pred: &Array<f32>, target: &Array<f32>) -> f32 {
+    let num_samples = pred.dims()[0] as usize;
+    let (p, t) = (utils::array_to_vec(pred), utils::array_to_vec(target));
+    let total: f32 = (0..num_samples)
+        .map(|i| {
+            let (mut dot, mut pp, mut tt) = (0.0f32, 0.0f32, 0.0f32);
+            for j in (i..p.len()).step_by(num_samples) {
+                dot += p[j] * t[j];
+                pp += p[j] * p[j];
+                tt += t[j] * t[j];
+            }
+            dot / (pp.sqrt() * tt.sqrt()).max(EPSILON)
+        })
+        .sum();
+    total / num_samples as f32
+}
+
+/// Peak signal to noise ratio in dB, the peak is the range of the targets
+pub fn psnr(pred: &Array<f32>, target: &Array<f32>) -> f32 {
+    let range = data_range(&utils::array_to_vec(target));
+    let mse = rmse(pred, target).powi(2);
+    10.0 * (range * range / mse.max(EPSILON)).log10()
+}
+
+/// Mean structural similarity of [batch, height, width, channels] images
+///
+/// Uses the mean over 7x7 uniform windows (valid positions only) of every
+/// channel, with the dynamic range taken from the targets.
+pub fn ssim(pred: &Array<f32>, target: &Array<f32>) -> f32 {
+    const WINDOW: usize = 7;
+    let dims = pred.dims();
+    let (n, h, w, c) = (
+        dims[0] as usize,
+        dims[1] as usize,
+        dims[2] as usize,
+        dims[3] as usize,
+    );
+    let (p, t) = (utils::array_to_vec(pred), utils::array_to_vec(target));
+    let range = data_range(&t);
+    let (c1, c2) = ((0.01 * range).powi(2), (0.03 * range).powi(2));
+    let win = WINDOW.min(h).min(w);
+    let at = |values: &Vec<f32>, b: usize, i: usize, j: usize, k: usize| {
+        values[b + n * (i + h * (j + w * k))]
+    };
+
+    let mut total = 0.0f32;
+    let mut count = 0usize;
+    for b in 0..n {
+        for k in 0..c {
+            for i in 0..=(h - win) {
+                for j in 0..=(w - win) {
+                    let mut window_p = Vec::with_capacity(win * win);
+                    let mut window_t = Vec::with_capacity(win * win);
+                    for di in 0..win {
+                        for dj in 0..win {
+                            window_p.push(at(&p, b, i + di, j + dj, k));
+                            window_t.push(at(&t, b, i + di, j + dj, k));
+                        }
+                    }
+                    let (mp, mt) = (mean(&window_p), mean(&window_t));
+                    let cov = mean(
+                        &window_p
+                            .iter()
+                            .zip(window_t.iter())
+                            .map(|(x, y)| (x - mp) * (y - mt))
+                            .collect::<Vec<f32>>(),
+                    );
+                    total += ((2.0 * mp * mt + c1) * (2.0 * cov + c2))
+                        / ((mp * mp + mt * mt + c1)
+                            * (variance(&window_p) + variance(&window_t) + c2));
+                    count += 1;
+                }
+            }
+        }
+    }
+    total / count as f32
+}
+
+/// Predicted & true class of every sample
+///
+/// A single output column is a binary probability thresholded at 0.5,
+/// otherwise the class is the argmax over dim 1.
+fn classes(pred: &Array<f32>, target: &Array<f32>) -> (Vec<usize>, Vec<usize>, usize) {
+    let dims = pred.dims();
+    let (n, k) = (dims[0] as usize, dims[1] as usize);
+    let (p, t) = (utils::array_to_vec(pred), utils::array_to_vec(target));
+    let argmax = |values: &Vec<f32>, i: usize| {
+        if k == 1 {
+            (values[i] >= 0.5) as usize
+        } else {
+            (0..k)
+                .max_by(|&a, &b| values[i + n * a].total_cmp(&values[i + n * b]))
+                .unwrap()
+        }
+    };
+    let predicted = (0..n).map(|i| argmax(&p, i)).collect();
+    let actual = (0..n).map(|i| argmax(&t, i)).collect();
+    (predicted, actual, k.max(2))
+}
+
+/// Fraction of correctly classified samples
+pub fn accuracy(pred: &Array<f32>, target: &Array<f32>) -> f32 {
+    let (predicted, actual, _) = classes(pred, target);
+    let correct = predicted
+        .iter()
+        .zip(actual.iter())
+        .filter(|(p, a)| p == a)
+        .count();
+    correct as f32 / predicted.len() as f32
+}
+
+/// Per class (precision, recall, f1), the positive class only for binary outputs
+fn class_scores(pred: &Array<f32>, target: &Array<f32>) -> Vec<(f32, f32, f32)> {
+    let (predicted, actual, num_classes) = classes(pred, target);
+    let scored = if pred.dims()[1] == 1 {
+        1..2
+    } else {
+        0..num_classes
+    };
+    scored
+        .map(|class| {
+            let count = |p: bool, a: bool| {
+                predicted
+                    .iter()
+                    .zip(actual.iter())
+                    .filter(|(x, y)| (**x == class) == p && (**y == class) == a)
+                    .count() as f32
+            };
+            let (tp, fp, fn_) = (count(true, true), count(true, false), count(false, true));
+            let precision = tp / (tp + fp).max(1.0);
+            let recall = tp / (tp + fn_).max(1.0);
+            let f1 = 2.0 * precision * recall / (precision + recall).max(EPSILON);
+            (precision, recall, f1)
+        })
+        .collect()
+}
+
+/// Macro average of one of the class scores
+fn macro_average(scores: Vec<(f32, f32, f32)>, score: fn(&(f32, f32, f32)) -> f32) -> f32 {
+    scores.iter().map(score).sum::<f32>() / scores.len() as f32
+}
+
+/// Precision of the positive class (binary) or macro averaged over classes
+pub fn precision(pred: &Array<f32>, target: &Array<f32>) -> f32 {
+    macro_average(class_scores(pred, target), |s| s.0)
+}
+
+/// Recall of the positive class (binary) or macro averaged over classes
+pub fn recall(pred: &Array<f32>, target: &Array<f32>) -> f32 {
+    macro_average(class_scores(pred, target), |s| s.1)
+}
+
+/// F1 score of the positive class (binary) or macro averaged over classes
+pub fn f1(pred: &Array<f32>, target: &Array<f32>) -> f32 {
+    macro_average(class_scores(pred, target), |s| s.2)
+}
+
+fn mean(values: &[f32]) -> f32 {
+    values.iter().sum::<f32>() / values.len() as f32
+}
+
+fn variance(values: &[f32]) -> f32 {
+    let m = mean(values);
+    values.iter().map(|v| (v - m) * (v - m)).sum::<f32>() / values.len() as f32
+}
+
+fn data_range(values: &[f32]) -> f32 {
+    let min = values.iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    (max - min).max(EPSILON)
+}
+
+/// Helper to provide a metric from a string
+///
+/// "ssim" needs [batch, height, width, channels] Arrays, flat [batch, features]
+/// outputs are viewed as square single channel images.
+pub fn get_metric(name: &str, pred: &Array<f32>, target: &Array<f32>) -> Result<f32, HALError> {
+    match name {
+        "mae" => Ok(mae(pred, target)),
+        "rmse" => Ok(rmse(pred, target)),
+        "r2" => Ok(r2(pred, target)),
+        "explained_variance" => Ok(explained_variance(pred, target)),
+        "cosine" => Ok(cosine(pred, target)),
+        "psnr" => Ok(psnr(pred, target)),
+        "ssim" => {
+            let dims = pred.dims();
+            if dims[2] > 1 {
+                return Ok(ssim(pred, target));
+            }
+            let side = (dims[1] as f64).sqrt() as u64;
+            if side * side != dims[1] {
+                return Err(HALError::UNKNOWN);
+            }
+            let image = af::Dim4::new(&[dims[0], side, side, 1]);
+            Ok(ssim(&af::moddims(pred, image), &af::moddims(target, image)))
+        }
+        "accuracy" => Ok(accuracy(pred, target)),
+        "precision" => Ok(precision(pred, target)),
+        "recall" => Ok(recall(pred, target)),
+        "f1" => Ok(f1(pred, target)),
+        _ => Err(HALError::UNKNOWN),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use af::Dim4;
+
+    #[test]
+    fn perfect_reconstruction() {
+        let values = (0..16).map(|v| v as f32 / 16.0).collect::<Vec<f32>>();
+        let target = utils::vec_to_array(values, Dim4::new(&[1, 16, 1, 1]));
+        assert_eq!(get_metric("mae", &target, &target).unwrap(), 0.0);
+        assert!((get_metric("r2", &target, &target).unwrap() - 1.0).abs() < 1e-5);
+        assert!((get_metric("ssim", &target, &target).unwrap() - 1.0).abs() < 1e-5);
+        assert!(get_metric("unknown", &target, &target).is_err());
+    }
+
+    #[test]
+    fn binary_classification_scores() {
+        let pred = utils::vec_to_array(vec![0.9, 0.8, 0.2, 0.1], Dim4::new(&[4, 1, 1, 1]));
+        let target = utils::vec_to_array(vec![1.0, 0.0, 1.0, 0.0], Dim4::new(&[4, 1, 1, 1]));
+        assert_eq!(accuracy(&pred, &target), 0.5);
+        assert_eq!(precision(&pred, &target), 0.5);
+        assert_eq!(recall(&pred, &target), 0.5);
+        assert_eq!(f1(&pred, &target), 0.5);
+    }
+}
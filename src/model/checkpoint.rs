@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+
+use af::{Array, Dim4};
+use serde::{Deserialize, Serialize};
+
+use crate::data::Transform;
+use crate::error::HALError;
+use crate::utils;
+
+/// A scalar layer param, `Model::add` receives it as a string
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl ParamValue {
+    /// Reads back a string param, numbers & booleans become typed values again
+    pub fn parse(value: &str) -> ParamValue {
+        if let Ok(v) = value.parse::<i64>() {
+            ParamValue::Integer(v)
+        } else if let Ok(v) = value.parse::<f64>() {
+            ParamValue::Float(v)
+        } else if let Ok(v) = value.parse::<bool>() {
+            ParamValue::Bool(v)
+        } else {
+            ParamValue::Text(value.to_string())
+        }
+    }
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParamValue::Bool(v) => write!(f, "{}", v),
+            ParamValue::Integer(v) => write!(f, "{}", v),
+            ParamValue::Float(v) => write!(f, "{}", v),
+            ParamValue::Text(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// The type & params a layer was added with (see `Model::add`)
+///
+/// Layers added with string params keep them parsed by `ParamValue::parse`,
+/// layers added from a config (`Sequential::try_add_config`) keep its values.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LayerConfig {
+    pub layer: String,
+    pub params: BTreeMap<String, ParamValue>,
+}
+
+impl LayerConfig {
+    /// The params as handed to `Model::add`
+    pub fn string_params(&self) -> HashMap<&str, String> {
+        self.params
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.to_string()))
+            .collect()
+    }
+}
+
+/// Host copy of a parameter Array
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArrayData {
+    pub dims: [u64; 4],
+    pub values: Vec<f32>,
+}
+
+impl ArrayData {
+    pub fn from_array(values: &Array<f32>) -> ArrayData {
+        ArrayData {
+            dims: *values.dims().get(),
+            values: utils::array_to_vec(values),
+        }
+    }
+
+    pub fn to_array(&self) -> Array<f32> {
+        utils::raw_to_array(&self.values, Dim4::new(&self.dims))
+    }
+}
+
+/// A serializable snapshot of a trained model
+///
+/// Besides the architecture & parameters it carries the fitted data
+/// transforms, so inference can use the exact scaling used in training, and
+/// the fitted anomaly threshold.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub loss: String,
+    pub layers: Vec<LayerConfig>,
+    // parameters ordered as [W0, b0, .. WN, bN]
+    pub arrays: Vec<ArrayData>,
+    pub input_transform: Option<Transform>,
+    pub target_transform: Option<Transform>,
+    /// Reconstruction score above which a sample is anomalous (see `AnomalyDetector`)
+    #[serde(default)]
+    pub anomaly_threshold: Option<f32>,
+}
+
+impl Checkpoint {
+    /// Writes the checkpoint as json
+    pub fn save(&self, path: &str) -> Result<(), HALError> {
+        let json = serde_json::to_string(self).map_err(|_| HALError::UNKNOWN)?;
+        fs::write(path, json).map_err(|_| HALError::IO_ERROR)
+    }
+
+    /// Reads a checkpoint written by `save`
+    pub fn load(path: &str) -> Result<Checkpoint, HALError> {
+        let json = fs::read_to_string(path).map_err(|_| HALError::IO_ERROR)?;
+        serde_json::from_str(&json).map_err(|_| HALError::PARSE_ERROR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Sequential;
+    use crate::optimizer::get_optimizer_with_defaults;
+    use crate::test_utils::{dense_model, sgd_model};
+
+    #[test]
+    fn save_and_restore() {
+        let model = dense_model(&[(4, 2)], "tanh");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.json");
+        model.checkpoint().save(path.to_str().unwrap()).unwrap();
+        let checkpoint = Checkpoint::load(path.to_str().unwrap()).unwrap();
+        let restored =
+            Sequential::from_checkpoint(&checkpoint, get_optimizer_with_defaults("sgd").unwrap());
+
+        let expected = model.checkpoint().arrays;
+        let actual = restored.checkpoint().arrays;
+        assert_eq!(expected.len(), actual.len());
+        for (e, a) in expected.iter().zip(actual.iter()) {
+            assert_eq!(e.dims, a.dims);
+            assert_eq!(e.values, a.values);
+        }
+    }
+
+    #[test]
+    fn transforms_round_trip() {
+        use crate::data::TransformKind;
+        use af::Dim4;
+
+        let values =
+            utils::vec_to_array((0..8).map(|v| v as f32).collect(), Dim4::new(&[4, 2, 1, 1]));
+        let mut model = sgd_model();
+        model.set_transforms(
+            Some(Transform::fit(TransformKind::MinMax, &values)),
+            Some(Transform::fit(TransformKind::Standard, &values)),
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transforms.json");
+        model.checkpoint().save(path.to_str().unwrap()).unwrap();
+        let checkpoint = Checkpoint::load(path.to_str().unwrap()).unwrap();
+        let restored =
+            Sequential::from_checkpoint(&checkpoint, get_optimizer_with_defaults("sgd").unwrap());
+
+        let (input_transform, target_transform) = restored.transforms();
+        for transform in &[input_transform.unwrap(), target_transform.unwrap()] {
+            let scaled = transform.transform(&values);
+            let restored_values = utils::array_to_vec(&transform.inverse_transform(&scaled));
+            for (expected, actual) in utils::array_to_vec(&values).iter().zip(restored_values) {
+                assert!((expected - actual).abs() < 1e-5);
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::HALError;
+
+/// The record of a training run returned by `fit`
+///
+/// Iteration series have one entry per minibatch, epoch series one entry per
+/// epoch. `metrics` holds every other epoch log (eg: validation metrics) by name.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct History {
+    /// Mean loss over the time slices of every minibatch
+    pub iteration_losses: Vec<f32>,
+    /// L2 norm of the (batch averaged) gradients before every update
+    pub grad_norms: Vec<f32>,
+    /// Mean minibatch loss of every epoch
+    pub epoch_losses: Vec<f32>,
+    /// Validation loss of every epoch, empty without validation data
+    pub validation_losses: Vec<f32>,
+    /// Mean activity penalty of every epoch, empty without activity regularizers
+    #[serde(default)]
+    pub activity_losses: Vec<f32>,
+    pub metrics: BTreeMap<String, Vec<f32>>,
+    /// Base learning rate used during every epoch
+    pub learning_rates: Vec<f32>,
+    /// Wall-clock seconds spent on every epoch (including validation)
+    pub epoch_times: Vec<f32>,
+}
+
+impl History {
+    /// Appends the logs of one epoch, every key but loss/val_loss/activity_loss/lr is a metric
+    pub(crate) fn record_epoch(&mut self, logs: &HashMap<String, f32>, seconds: f32) {
+        for (key, value) in logs {
+            match key.as_str() {
+                "loss" => self.epoch_losses.push(*value),
+                "val_loss" => self.validation_losses.push(*value),
+                "activity_loss" => self.activity_losses.push(*value),
+                "lr" => self.learning_rates.push(*value),
+                _ => self.metrics.entry(key.clone()).or_default().push(*value),
+            }
+        }
+        self.epoch_times.push(seconds);
+    }
+
+    /// Number of recorded epochs
+    pub fn num_epochs(&self) -> usize {
+        self.epoch_losses.len()
+    }
+
+    /// Writes one row per epoch: epoch, loss, val_loss, lr, time & the metrics
+    ///
+    /// An activity_loss column follows the loss when activity penalties were used.
+    pub fn save_csv(&self, path: &str) -> Result<(), HALError> {
+        let mut writer = csv::Writer::from_path(path).map_err(|_| HALError::IO_ERROR)?;
+        let with_activity = !self.activity_losses.is_empty();
+        let mut header = vec!["epoch", "loss"];
+        if with_activity {
+            header.push("activity_loss");
+        }
+        header.extend(&["val_loss", "lr", "time"]);
+        header.extend(self.metrics.keys().map(|k| k.as_str()));
+        writer
+            .write_record(&header)
+            .map_err(|_| HALError::IO_ERROR)?;
+
+        let cell = |values: &Vec<f32>, epoch: usize| {
+            values.get(epoch).map_or(String::new(), |v| v.to_string())
+        };
+        for epoch in 0..self.num_epochs() {
+            let mut row = vec![epoch.to_string(), cell(&self.epoch_losses, epoch)];
+            if with_activity {
+                row.push(cell(&self.activity_losses, epoch));
+            }
+            row.extend(vec![
+                cell(&self.validation_losses, epoch),
+                cell(&self.learning_rates, epoch),
+                cell(&self.epoch_times, epoch),
+            ]);
+            row.extend(self.metrics.values().map(|values| cell(values, epoch)));
+            writer.write_record(&row).map_err(|_| HALError::IO_ERROR)?;
+        }
+        writer.flush().map_err(|_| HALError::IO_ERROR)
+    }
+
+    /// Writes one row per minibatch: iteration, loss & grad_norm
+    pub fn save_iterations_csv(&self, path: &str) -> Result<(), HALError> {
+        let mut writer = csv::Writer::from_path(path).map_err(|_| HALError::IO_ERROR)?;
+        writer
+            .write_record(&["iteration", "loss", "grad_norm"])
+            .map_err(|_| HALError::IO_ERROR)?;
+        for (iteration, (loss, norm)) in self
+            .iteration_losses
+            .iter()
+            .zip(self.grad_norms.iter())
+            .enumerate()
+        {
+            writer
+                .write_record(&[iteration.to_string(), loss.to_string(), norm.to_string()])
+                .map_err(|_| HALError::IO_ERROR)?;
+        }
+        writer.flush().map_err(|_| HALError::IO_ERROR)
+    }
+
+    /// Writes the whole history as JSON
+    pub fn save_json(&self, path: &str) -> Result<(), HALError> {
+        let json = serde_json::to_string(self).map_err(|_| HALError::PARSE_ERROR)?;
+        fs::write(path, json).map_err(|_| HALError::IO_ERROR)
+    }
+
+    pub fn load_json(path: &str) -> Result<History, HALError> {
+        let json = fs::read_to_string(path).map_err(|_| HALError::IO_ERROR)?;
+        serde_json::from_str(&json).map_err(|_| HALError::PARSE_ERROR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_has_a_row_per_epoch() {
+        let history = History {
+            epoch_losses: vec![0.5, 0.25],
+            learning_rates: vec![0.1, 0.1],
+            epoch_times: vec![1.0, 1.0],
+            metrics: [("val_mae".to_string(), vec![0.3, 0.2])]
+                .iter()
+                .cloned()
+                .collect(),
+            ..History::default()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.csv");
+        history.save_csv(path.to_str().unwrap()).unwrap();
+        let lines = fs::read_to_string(&path).unwrap();
+        let lines = lines.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[0], "epoch,loss,val_loss,lr,time,val_mae");
+        assert_eq!(lines[2], "1,0.25,,0.1,1,0.2");
+    }
+
+    #[test]
+    fn activity_loss_is_reported_separately() {
+        let mut history = History::default();
+        let logs = [("loss", 0.5), ("activity_loss", 0.1), ("lr", 0.01)]
+            .iter()
+            .map(|(k, v)| (k.to_string(), *v))
+            .collect::<HashMap<String, f32>>();
+        history.record_epoch(&logs, 1.0);
+        assert_eq!(history.epoch_losses, vec![0.5]);
+        assert_eq!(history.activity_losses, vec![0.1]);
+        assert!(history.metrics.is_empty());
+    }
+}
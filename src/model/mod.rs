@@ -1,8 +1,13 @@
+mod checkpoint;
+mod history;
 mod sequential;
 use std::collections::HashMap;
 
+pub use self::checkpoint::{ArrayData, Checkpoint, LayerConfig, ParamValue};
+pub use self::history::History;
 pub use self::sequential::Sequential;
 use crate::data::DataSouce;
+use crate::error::HALError;
 use crate::optimizer::Optimizer;
 
 use arrayfire::Array;
@@ -38,7 +43,7 @@ pub trait Model {
     ///
     /// # Return Values
     ///
-    /// Vector of losses
+    /// The training history (losses, validation, metrics, learning rate, time & gradient norms)
     fn fit<T>(
         &mut self,
         source: &T,
@@ -46,7 +51,7 @@ pub trait Model {
         batch_size: u64,
         loss_indices: Option<&Vec<bool>>,
         verbose: bool,
-    ) -> Vec<f32>
+    ) -> History
     where
         T: DataSouce;
 
@@ -87,6 +92,52 @@ pub trait Model {
         loss_indices: Option<&Vec<bool>>,
     ) -> Vec<f32>;
 
+    /// Computes the evaluation metrics over one pass of the test iterator
+    ///
+    /// Runs in inference mode: no inputs/outputs are cached for backprop and
+    /// neither the gradients nor the optimizer state are touched
+    ///
+    /// # Parameters
+    ///
+    /// - `source` is the datasource
+    /// - `batch_size` is the minibatch size
+    ///
+    /// # Return Values
+    ///
+    /// Map of metric name to its sample weighted mean (eg: "loss"), an error
+    /// for a zero batch size
+    fn evaluate<T>(&self, source: &T, batch_size: u64) -> Result<HashMap<String, f32>, HALError>
+    where
+        T: DataSouce;
+
+    /// Runs inference on the provided inputs in chunks of `batch_size` samples
+    ///
+    /// # Parameters
+    ///
+    /// - `inputs` is an array of activations [samples, feature, time]
+    /// - `batch_size` is the number of samples per chunk
+    ///
+    /// # Return Values
+    ///
+    /// The model outputs of all samples [samples, feature, time], an error for
+    /// a zero batch size or inputs without samples
+    fn predict(&self, inputs: &Array<f32>, batch_size: u64) -> Result<Array<f32>, HALError>;
+
+    /// Runs inference on one pass of the test iterator
+    ///
+    /// # Parameters
+    ///
+    /// - `source` is the datasource
+    /// - `batch_size` is the minibatch size
+    ///
+    /// # Return Values
+    ///
+    /// The concatenated model outputs [samples, feature, time], an error for a
+    /// zero batch size or a source without test samples
+    fn predict_batches<T>(&self, source: &T, batch_size: u64) -> Result<Array<f32>, HALError>
+    where
+        T: DataSouce;
+
     /// Show model info
     ///
     ///
@@ -1,10 +1,22 @@
 use std::collections::HashMap;
+use std::ops::Range;
+use std::time::Instant;
 
+use crate::activations;
+use crate::callbacks::Callback;
+use crate::constraints::{self, Constraint};
+use crate::data::{self, Data, DataSouce, Transform};
+use crate::error::HALError;
+use crate::initializations;
 use crate::layer::{Dense, Layer};
 use crate::loss;
-use crate::model::Model;
+use crate::metrics;
+use crate::model::{ArrayData, Checkpoint, History, LayerConfig, Model, ParamValue};
 use crate::optimizer::{Optimizer, SGD};
 use crate::params::{DenseGenerator, ParamManager};
+use crate::regularizers::{
+    self, ActivityRegularizer, ContractivePenalty, Regularizer, WeightPenalty,
+};
 use crate::utils;
 
 use af::print;
@@ -16,6 +28,18 @@ pub struct Sequential {
     param_manager: ParamManager,
     optimizer: Box<dyn Optimizer>,
     loss: String,
+    activity_regularizers: Vec<Option<ActivityRegularizer>>,
+    activity_loss: f32,
+    regularizers: Vec<Box<dyn Regularizer>>,
+    constraints: Vec<(usize, Constraint)>,
+    layer_configs: Vec<LayerConfig>,
+    callbacks: Vec<Box<dyn Callback>>,
+    stop_training: bool,
+    training_error: Option<HALError>,
+    metrics: Vec<String>,
+    input_transform: Option<Transform>,
+    target_transform: Option<Transform>,
+    anomaly_threshold: Option<f32>,
 }
 
 impl Default for Sequential {
@@ -25,10 +49,491 @@ impl Default for Sequential {
             param_manager: ParamManager::default(),
             optimizer: Box::new(SGD::default()),
             loss: "mse".to_string(),
+            activity_regularizers: Vec::new(),
+            activity_loss: 0.0,
+            regularizers: Vec::new(),
+            constraints: Vec::new(),
+            layer_configs: Vec::new(),
+            callbacks: Vec::new(),
+            stop_training: false,
+            training_error: None,
+            metrics: Vec::new(),
+            input_transform: None,
+            target_transform: None,
+            anomaly_threshold: None,
         }
     }
 }
 
+impl Sequential {
+    /// Resolves a layer by the "name" it was added with or by its index
+    pub fn layer_index(&self, layer: &str) -> Option<usize> {
+        self.layer_configs
+            .iter()
+            .position(|config| {
+                config
+                    .params
+                    .get("name")
+                    .map_or(false, |name| name.to_string() == layer)
+            })
+            .or_else(|| layer.parse::<usize>().ok())
+            .filter(|index| *index < self.layers.len())
+    }
+
+    /// Returns the activity penalty of the last backward pass
+    ///
+    /// This is reported separately from the reconstruction loss returned by `backward`
+    /// and, like it, averaged over the time slices of the minibatch
+    pub fn activity_loss(&self) -> f32 {
+        self.activity_loss
+    }
+
+    /// Projects the constrained weights back onto their feasible set
+    fn apply_constraints(&self) {
+        for (layer_index, constraint) in &self.constraints {
+            let w = self.param_manager.get_weight(*layer_index, 0);
+            self.param_manager
+                .set_weight(*layer_index, 0, constraint.apply(&w));
+        }
+    }
+
+    /// Mean loss over the time slices of a prediction
+    fn loss_value(&self, predictions: &[Array<f32>], targets: &Array<f32>) -> f32 {
+        let loss_sum = predictions
+            .iter()
+            .enumerate()
+            .map(|(ind, pred)| {
+                let tar = af::slice(targets, ind as i64);
+                loss::get_loss(&self.loss, pred, &tar).unwrap()
+            })
+            .sum::<f32>();
+        loss_sum / predictions.len() as f32
+    }
+
+    /// Loss & selected metrics of a batch, each averaged over the time slices
+    ///
+    /// A metric that is not defined for the data (eg: ssim of non-square
+    /// images) is left out of the logs.
+    fn batch_logs(&self, predictions: &[Array<f32>], targets: &Array<f32>) -> HashMap<String, f32> {
+        let mut logs = HashMap::new();
+        logs.insert("loss".to_string(), self.loss_value(predictions, targets));
+        for name in &self.metrics {
+            let values = predictions
+                .iter()
+                .enumerate()
+                .map(|(ind, pred)| {
+                    let tar = af::slice(targets, ind as i64);
+                    metrics::get_metric(name, pred, &tar)
+                })
+                .collect::<Result<Vec<f32>, HALError>>();
+            if let Ok(values) = values {
+                let value = values.iter().sum::<f32>() / predictions.len() as f32;
+                logs.insert(name.clone(), value);
+            }
+        }
+        logs
+    }
+
+    /// L2 norm over all the accumulated gradients
+    fn grad_norm(&self) -> f32 {
+        self.param_manager
+            .get_all_deltas()
+            .iter()
+            .map(|delta| af::sum_all(&af::mul(delta, delta, false)).0 as f32)
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    /// Runs the `layers` per time slice (dim 2)
+    ///
+    /// Training (`cache`) and inference share this unrolling, only training
+    /// caches the inputs/outputs of every slice for the backward pass.
+    fn unroll(&self, inputs: &Array<f32>, layers: Range<usize>, cache: bool) -> Vec<Array<f32>> {
+        (0..inputs.dims()[2])
+            .map(|t| {
+                let mut activate = af::slice(inputs, t as i64);
+                for i in layers.clone() {
+                    let params = self.param_manager.get_params(i);
+                    activate = match cache {
+                        true => self.layers[i].forward(params, &activate),
+                        false => self.layers[i].predict(params, &activate),
+                    };
+                }
+                activate
+            })
+            .collect()
+    }
+
+    /// Inference pass of all the layers per time slice, nothing is cached
+    fn predict_slices(&self, inputs: &Array<f32>) -> Vec<Array<f32>> {
+        self.unroll(inputs, 0..self.layers.len(), false)
+    }
+
+    /// Inference pass of the layers up to (and including) `last_layer` per time slice
+    fn predict_slices_until(&self, inputs: &Array<f32>, last_layer: usize) -> Vec<Array<f32>> {
+        self.unroll(inputs, 0..last_layer + 1, false)
+    }
+
+    /// Runs inference over `num_samples` samples served by `next_batch`
+    ///
+    /// `visit` receives every batch with its per time slice predictions, returns
+    /// false when the source ran out of batches before `num_samples` samples.
+    pub(crate) fn for_each_batch<F, G>(
+        &self,
+        num_samples: u64,
+        batch_size: u64,
+        next_batch: F,
+        visit: G,
+    ) -> bool
+    where
+        F: FnMut(u64) -> Option<Data>,
+        G: FnMut(&Data, &[Array<f32>]),
+    {
+        let last_layer = self.layers.len() - 1;
+        self.for_each_encoded(num_samples, batch_size, last_layer, next_batch, visit)
+    }
+
+    /// Same as `for_each_batch` with the outputs of `last_layer` (eg: a bottleneck)
+    pub(crate) fn for_each_encoded<F, G>(
+        &self,
+        num_samples: u64,
+        batch_size: u64,
+        last_layer: usize,
+        mut next_batch: F,
+        mut visit: G,
+    ) -> bool
+    where
+        F: FnMut(u64) -> Option<Data>,
+        G: FnMut(&Data, &[Array<f32>]),
+    {
+        let mut count = 0u64;
+        while count < num_samples {
+            let batch = match next_batch(batch_size) {
+                Some(batch) => truncate_batch(batch, num_samples - count),
+                None => return false,
+            };
+            count += batch.input.dims()[0];
+            visit(&batch, &self.predict_slices_until(&batch.input, last_layer));
+        }
+        true
+    }
+
+    /// Runs the inputs through the layers up to (and including) `last_layer`
+    ///
+    /// Returns the [samples, feature, time] outputs of that layer, ie: the latent
+    /// codes when `last_layer` is the bottleneck of an autoencoder.
+    pub fn encode(&self, inputs: &Array<f32>, last_layer: usize) -> Array<f32> {
+        join_slices(&self.predict_slices_until(inputs, last_layer))
+    }
+
+    /// Runs latent codes through the layers from `first_layer` to the output
+    ///
+    /// The counterpart of `encode`: `first_layer` is the first layer of the
+    /// decoder, ie: the one right after the bottleneck.
+    pub fn decode(&self, codes: &Array<f32>, first_layer: usize) -> Array<f32> {
+        join_slices(&self.unroll(codes, first_layer..self.layers.len(), false))
+    }
+
+    /// Per-sample loss (without penalties) averaged over the time slices
+    pub fn sample_losses(&self, predictions: &[Array<f32>], targets: &Array<f32>) -> Vec<f32> {
+        let mut losses = vec![0.0f32; predictions[0].dims()[0] as usize];
+        for (ind, pred) in predictions.iter().enumerate() {
+            let tar = af::slice(targets, ind as i64);
+            let slice_losses = loss::get_loss_per_sample(&self.loss, pred, &tar).unwrap();
+            for (total, value) in losses.iter_mut().zip(utils::array_to_vec(&slice_losses)) {
+                *total += value / predictions.len() as f32;
+            }
+        }
+        losses
+    }
+
+    /// Returns the fitted anomaly threshold, persisted in checkpoints
+    pub fn anomaly_threshold(&self) -> Option<f32> {
+        self.anomaly_threshold
+    }
+
+    pub fn set_anomaly_threshold(&mut self, threshold: Option<f32>) {
+        self.anomaly_threshold = threshold;
+    }
+
+    /// Sample weighted mean of the batch logs over `num_samples` samples served by `next_batch`
+    fn mean_logs<F>(
+        &self,
+        num_samples: u64,
+        batch_size: u64,
+        next_batch: F,
+    ) -> Option<HashMap<String, f32>>
+    where
+        F: FnMut(u64) -> Option<Data>,
+    {
+        if num_samples == 0 {
+            return None;
+        }
+
+        let mut sums = LogSums::new();
+        let complete =
+            self.for_each_batch(num_samples, batch_size, next_batch, |batch, predictions| {
+                let logs = self.batch_logs(predictions, &batch.target);
+                accumulate_logs(&mut sums, &logs, batch.input.dims()[0] as f32);
+            });
+        if !complete {
+            return None;
+        }
+        Some(average_logs(sums))
+    }
+
+    /// Loss & metrics over one pass of the validation iterator (None without validation data)
+    fn validation_logs<T: DataSouce>(
+        &self,
+        source: &T,
+        batch_size: u64,
+    ) -> Option<HashMap<String, f32>> {
+        self.mean_logs(source.num_validation_samples(), batch_size, |n| {
+            source.get_validation_iter(n)
+        })
+    }
+
+    /// Returns the data transforms the model was trained with, persisted in checkpoints
+    pub fn transforms(&self) -> (Option<&Transform>, Option<&Transform>) {
+        (
+            self.input_transform.as_ref(),
+            self.target_transform.as_ref(),
+        )
+    }
+
+    /// Attaches the fitted input & target transforms (eg: of a `TransformedSource`)
+    pub fn set_transforms(
+        &mut self,
+        input_transform: Option<Transform>,
+        target_transform: Option<Transform>,
+    ) {
+        self.input_transform = input_transform;
+        self.target_transform = target_transform;
+    }
+
+    /// Selects a metric (see `metrics::METRICS`) computed in `fit` and `evaluate`
+    pub fn add_metric(&mut self, name: &str) -> Result<(), HALError> {
+        if !metrics::METRICS.contains(&name) {
+            return Err(HALError::UNKNOWN);
+        }
+        if !self.metrics.iter().any(|m| m == name) {
+            self.metrics.push(name.to_string());
+        }
+        Ok(())
+    }
+
+    /// Returns a serializable snapshot of the architecture and parameters
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            loss: self.loss.clone(),
+            layers: self.layer_configs.clone(),
+            arrays: self
+                .param_manager
+                .get_all_arrays()
+                .iter()
+                .map(ArrayData::from_array)
+                .collect(),
+            input_transform: self.input_transform.clone(),
+            target_transform: self.target_transform.clone(),
+            anomaly_threshold: self.anomaly_threshold,
+        }
+    }
+
+    /// Rebuilds a model from a checkpoint, the optimizer state starts fresh
+    pub fn from_checkpoint(checkpoint: &Checkpoint, optimizer: Box<dyn Optimizer>) -> Sequential {
+        let mut model = Sequential::new(optimizer, &checkpoint.loss);
+        for config in &checkpoint.layers {
+            model.add(&config.layer, config.string_params());
+        }
+        model.layer_configs = checkpoint.layers.clone();
+
+        let num_arrays = model.param_manager.get_all_arrays().len();
+        assert!(
+            num_arrays == checkpoint.arrays.len(),
+            "checkpoint holds {} arrays but the model expects {}",
+            checkpoint.arrays.len(),
+            num_arrays
+        );
+        model.restore(checkpoint);
+        model.set_transforms(
+            checkpoint.input_transform.clone(),
+            checkpoint.target_transform.clone(),
+        );
+        model.anomaly_threshold = checkpoint.anomaly_threshold;
+        model
+    }
+
+    /// Loads the parameters of a checkpoint taken from the same architecture
+    pub fn restore(&mut self, checkpoint: &Checkpoint) {
+        for (ind, array) in checkpoint.arrays.iter().enumerate() {
+            self.param_manager
+                .set_array_from_index(array.to_array(), ind);
+        }
+    }
+
+    /// Fallible `Model::add`, eg: for layers read from a config file
+    ///
+    /// Returns a PARSE_ERROR, and adds nothing, when the params are invalid.
+    pub fn try_add(&mut self, layer: &str, params: HashMap<&str, String>) -> Result<(), HALError> {
+        self.validate_layer(layer, &params)
+            .map_err(|_| HALError::PARSE_ERROR)?;
+        self.add(layer, params);
+        Ok(())
+    }
+
+    /// Fallible add of a layer config, its typed params are kept in `layer_configs`
+    pub fn try_add_config(&mut self, config: &LayerConfig) -> Result<(), HALError> {
+        self.try_add(&config.layer, config.string_params())?;
+        if let Some(last) = self.layer_configs.last_mut() {
+            *last = config.clone();
+        }
+        Ok(())
+    }
+
+    /// Checks the params of a layer before it is added, returns what is wrong
+    fn validate_layer(&self, layer: &str, params: &HashMap<&str, String>) -> Result<(), String> {
+        if layer != "dense" {
+            return Err(format!("not supported layer {}", layer));
+        }
+        let get = |key: &str| params.get(key).ok_or_else(|| format!("missing {}", key));
+        let size = |key: &str| -> Result<usize, String> {
+            get(key)?
+                .parse::<u64>()
+                .ok()
+                .filter(|size| *size > 0)
+                .map(|size| size as usize)
+                .ok_or_else(|| format!("{} must be a positive integer", key))
+        };
+        let known = |key: &str, names: &[&str]| -> Result<(), String> {
+            let name = get(key)?;
+            if names.contains(&name.as_str()) {
+                Ok(())
+            } else {
+                Err(format!("unknown {} {}", key, name))
+            }
+        };
+
+        let input_size = size("input_size")?;
+        let output_size = size("output_size")?;
+        if let Some(weight) = params.get("contractive_weight") {
+            let activation = get("activation")?;
+            if activation != "sigmoid" && activation != "tanh" {
+                return Err(format!(
+                    "contractive_weight requires a sigmoid or tanh activation, got {}",
+                    activation
+                ));
+            }
+            weight
+                .parse::<f32>()
+                .map_err(|_| "contractive_weight must be a number".to_string())?;
+        }
+        known("activation", &activations::ACTIVATIONS)?;
+        known("b_init", &initializations::INITIALIZATIONS)?;
+
+        if let Some(name) = params.get("activity_regularizer") {
+            regularizers::get_activity_regularizer(name, params)
+                .map_err(|_| format!("invalid activity regularizer {}", name))?;
+        }
+        for (key, prefix) in &[("w_regularizer", "w"), ("b_regularizer", "b")] {
+            if let Some(name) = params.get(key) {
+                regularizers::get_weight_regularizer(name, prefix, params)
+                    .map_err(|_| format!("invalid {} {}", key, name))?;
+            }
+        }
+        if let Some(name) = params.get("w_constraint") {
+            constraints::get_constraint(name, params)
+                .map_err(|_| format!("invalid w_constraint {}", name))?;
+        }
+
+        let source = match params.get("tied_to") {
+            Some(source) => source,
+            None => return known("w_init", &initializations::INITIALIZATIONS),
+        };
+        let source_index = self
+            .layer_index(source)
+            .ok_or_else(|| format!("tied_to {} is not an earlier layer", source))?;
+        let source_config = &self.layer_configs[source_index];
+        if source_config.layer != "dense" || source_config.params.contains_key("tied_to") {
+            return Err(format!("tied_to {} must be an untied dense layer", source));
+        }
+        // the weight belongs to the source layer, penalties go there
+        for key in &["w_constraint", "w_regularizer", "contractive_weight"] {
+            if params.contains_key(key) {
+                return Err(format!(
+                    "{} is not supported on a tied layer, set it on layer {}",
+                    key, source
+                ));
+            }
+        }
+        let source_dims = self.param_manager.get_weight(source_index, 0).dims();
+        if source_dims[0] as usize != output_size || source_dims[1] as usize != input_size {
+            return Err(format!(
+                "tied layer sizes must be the transpose of layer {}",
+                source
+            ));
+        }
+        Ok(())
+    }
+
+    /// Adds a parameter penalty that is applied on every backward pass
+    pub fn add_regularizer(&mut self, regularizer: Box<dyn Regularizer>) {
+        self.regularizers.push(regularizer);
+    }
+
+    /// Adds a callback whose hooks are called by `fit`
+    pub fn add_callback(&mut self, callback: Box<dyn Callback>) {
+        self.callbacks.push(callback);
+    }
+
+    /// Requests `fit` to stop after the current minibatch
+    pub fn stop_training(&mut self) {
+        self.stop_training = true;
+    }
+
+    /// Records an error raised by a callback (eg: a failed write) and stops `fit`
+    pub fn fail_training(&mut self, error: HALError) {
+        self.training_error = Some(error);
+        self.stop_training = true;
+    }
+
+    /// Returns the error that ended the last `fit`, if any
+    pub fn training_error(&self) -> Option<&HALError> {
+        self.training_error.as_ref()
+    }
+
+    /// Returns whether training was requested to stop
+    pub fn is_stopping(&self) -> bool {
+        self.stop_training
+    }
+
+    /// Returns the type & params every layer was added with
+    pub fn layer_configs(&self) -> &[LayerConfig] {
+        &self.layer_configs
+    }
+
+    pub fn loss(&self) -> &str {
+        &self.loss
+    }
+
+    /// Returns the names of the metrics added with `add_metric`
+    pub fn metrics(&self) -> &[String] {
+        &self.metrics
+    }
+
+    /// Returns the parameters (and accumulated gradients) of all the layers
+    pub fn param_manager(&self) -> &ParamManager {
+        &self.param_manager
+    }
+
+    pub fn optimizer(&self) -> &dyn Optimizer {
+        self.optimizer.as_ref()
+    }
+
+    pub fn optimizer_mut(&mut self) -> &mut dyn Optimizer {
+        self.optimizer.as_mut()
+    }
+}
+
 impl Model for Sequential {
     fn new(optimizer: Box<dyn Optimizer>, loss: &str) -> Sequential {
         Sequential {
@@ -36,29 +541,84 @@ impl Model for Sequential {
             param_manager: ParamManager::default(),
             optimizer,
             loss: loss.to_string(),
+            activity_regularizers: Vec::new(),
+            activity_loss: 0.0,
+            regularizers: Vec::new(),
+            constraints: Vec::new(),
+            layer_configs: Vec::new(),
+            callbacks: Vec::new(),
+            stop_training: false,
+            training_error: None,
+            metrics: Vec::new(),
+            input_transform: None,
+            target_transform: None,
+            anomaly_threshold: None,
         }
     }
 
     fn add(&mut self, layer: &str, params: HashMap<&str, String>) {
+        if let Err(message) = self.validate_layer(layer, &params) {
+            panic!("{}", message);
+        }
         let input_size = params.get("input_size").unwrap().parse::<u64>().unwrap() as usize;
         let output_size = params.get("output_size").unwrap().parse::<u64>().unwrap() as usize;
         let activation = params.get("activation").unwrap();
-        let w_init = params.get("w_init").unwrap();
         let b_init = params.get("b_init").unwrap();
+        let activity_regularizer = params
+            .get("activity_regularizer")
+            .map(|name| regularizers::get_activity_regularizer(name, &params).unwrap());
+        let kernel_regularizer = params
+            .get("w_regularizer")
+            .map(|name| regularizers::get_weight_regularizer(name, "w", &params).unwrap());
+        let bias_regularizer = params
+            .get("b_regularizer")
+            .map(|name| regularizers::get_weight_regularizer(name, "b", &params).unwrap());
+        let constraint = params
+            .get("w_constraint")
+            .map(|name| constraints::get_constraint(name, &params).unwrap());
 
-        match layer {
-            "dense" => {
+        match params.get("tied_to") {
+            // decoder whose weight is the transpose of an earlier layer's weight
+            Some(source) => {
+                let source_index = self.layer_index(source).unwrap();
                 self.param_manager
-                    .add_dense(input_size, output_size, activation, w_init, b_init);
-                self.layers.push(Box::new(Dense {
-                    input_size,
-                    output_size,
-                }));
+                    .add_tied_dense(source_index, output_size, activation, b_init);
             }
-            _ => {
-                panic!("not supported layer!!");
+            None => {
+                let w_init = params.get("w_init").unwrap();
+                self.param_manager
+                    .add_dense(input_size, output_size, activation, w_init, b_init);
             }
         }
+        self.layer_configs.push(LayerConfig {
+            layer: layer.to_string(),
+            params: params
+                .iter()
+                .map(|(k, v)| (k.to_string(), ParamValue::parse(v)))
+                .collect(),
+        });
+        self.layers.push(Box::new(Dense {
+            input_size,
+            output_size,
+        }));
+        let layer_index = self.layers.len() - 1;
+        self.activity_regularizers.push(activity_regularizer);
+        if let Some(weight) = params.get("contractive_weight") {
+            self.regularizers.push(Box::new(ContractivePenalty {
+                layer_index,
+                weight: weight.parse::<f32>().unwrap(),
+            }));
+        }
+        if kernel_regularizer.is_some() || bias_regularizer.is_some() {
+            self.regularizers.push(Box::new(WeightPenalty {
+                layer_index,
+                kernel: kernel_regularizer,
+                bias: bias_regularizer,
+            }));
+        }
+        if let Some(constraint) = constraint {
+            self.constraints.push((layer_index, constraint));
+        }
     }
 
     fn fit<T>(
@@ -68,7 +628,7 @@ impl Model for Sequential {
         batch_size: u64,
         loss_indices: Option<&Vec<bool>>,
         verbose: bool,
-    ) -> Vec<f32>
+    ) -> History
     where
         T: crate::data::DataSouce,
     {
@@ -76,7 +636,7 @@ impl Model for Sequential {
         let data_params = source.info();
         let idims = data_params.input_dims;
         let tdims = data_params.target_dims;
-        let iters = data_params.num_samples as u64 / batch_size as u64;
+        let iters = source.num_batches(batch_size);
         println!(
             "\ntrain samples: {:?} | target samples: {:?} | batch size: {}",
             idims, tdims, batch_size
@@ -92,19 +652,43 @@ impl Model for Sequential {
         );
         assert!(self.layers.len() > 0, "Need at least one layer to fit!");
 
-        let mut lossvec = Vec::<f32>::new();
+        let mut history = History::default();
+        self.stop_training = false;
+        self.training_error = None;
+
+        // callbacks get the model itself, so they are moved out while training
+        let mut callbacks = std::mem::take(&mut self.callbacks);
+        for callback in callbacks.iter_mut() {
+            callback.on_train_begin(self);
+        }
+
         for epoch in 0..epochs {
+            // a callback may fail before the first epoch (eg: in on_train_begin)
+            if self.stop_training {
+                break;
+            }
+            for callback in callbacks.iter_mut() {
+                callback.on_epoch_begin(epoch, self);
+            }
+            let epoch_start = Instant::now();
+            let mut epoch_sums = LogSums::new();
+
             for iter in 0..iters {
+                for callback in callbacks.iter_mut() {
+                    callback.on_batch_begin(iter, self);
+                }
                 if verbose {
                     print!("\n[epoch: {}][iter: {}]", epoch, iter);
                 }
                 let minibatch = source.get_train_iter(batch_size);
+                // the last batch of an epoch may be partial
+                let current_batch_size = minibatch.input.dims()[0];
                 assert!(
-                    minibatch.input.dims()[0] == batch_size,
+                    current_batch_size > 0 && current_batch_size <= batch_size,
                     "Ensure that input dims are of batch rows"
                 );
                 assert!(
-                    minibatch.target.dims()[0] == batch_size,
+                    minibatch.target.dims()[0] == current_batch_size,
                     "Ensure that target dims are of batch rows"
                 );
 
@@ -116,36 +700,79 @@ impl Model for Sequential {
                 // print(&batch_target);
                 // print(&af::slice(&batch_target, 1));
                 current_loss_vec = self.backward(&a_t, &batch_target, loss_indices);
+                for callback in callbacks.iter_mut() {
+                    callback.on_backward_end(iter, self);
+                }
 
+                history
+                    .grad_norms
+                    .push(self.grad_norm() / current_batch_size as f32);
                 self.optimizer
-                    .update(&mut self.param_manager, batch_size as u64);
+                    .update(&mut self.param_manager, current_batch_size);
+                self.apply_constraints();
 
+                let loss_sum = current_loss_vec.iter().fold(0f32, |sum, val| sum + val);
+                let avg_loss = loss_sum / current_loss_vec.len() as f32;
                 if verbose {
-                    let loss_sum = current_loss_vec.iter().fold(0f32, |sum, val| sum + val);
-                    let avg_loss = loss_sum / current_loss_vec.len() as f32;
                     print!("{} ", avg_loss);
+                    if self.activity_regularizers.iter().any(|r| r.is_some()) {
+                        print!("[activity: {}] ", self.activity_loss);
+                    }
                 }
 
-                lossvec.extend(current_loss_vec);
+                history.iteration_losses.push(avg_loss);
+
+                // metrics are computed on the predictions the update was based on
+                let mut batch_logs = self.batch_logs(&a_t, batch_target);
+                batch_logs.insert("loss".to_string(), avg_loss);
+                if self.activity_regularizers.iter().any(|r| r.is_some()) {
+                    batch_logs.insert("activity_loss".to_string(), self.activity_loss);
+                }
+                accumulate_logs(&mut epoch_sums, &batch_logs, 1.0);
+                for callback in callbacks.iter_mut() {
+                    callback.on_batch_end(iter, &batch_logs, self);
+                }
+                if self.stop_training {
+                    break;
+                }
             }
-        }
 
-        lossvec
-    }
+            let mut epoch_logs = average_logs(epoch_sums);
+            epoch_logs.insert("lr".to_string(), self.optimizer.learning_rate());
+            if let Some(validation_logs) = self.validation_logs(source, batch_size) {
+                if verbose {
+                    print!(
+                        "\n[epoch: {}][validation loss: {}]",
+                        epoch, validation_logs["loss"]
+                    );
+                }
+                for (name, value) in validation_logs {
+                    epoch_logs.insert(format!("val_{}", name), value);
+                }
+            }
+            history.record_epoch(&epoch_logs, epoch_start.elapsed().as_secs_f32());
 
-    fn forward(&self, inputs: &Array<f32>) -> Vec<Array<f32>> {
-        let mut activate = inputs.clone();
+            for callback in callbacks.iter_mut() {
+                callback.on_epoch_end(epoch, &epoch_logs, self);
+            }
+            if self.stop_training {
+                break;
+            }
+        }
 
-        for i in 0..self.layers.len() {
-            let a = self.layers[i].forward(self.param_manager.get_params(i), &activate);
-            activate = a;
+        for callback in callbacks.iter_mut() {
+            callback.on_train_end(self);
         }
+        // keep callbacks that were added by other callbacks while training
+        callbacks.append(&mut self.callbacks);
+        self.callbacks = callbacks;
 
-        // return the collected outputs of the last layer
-        let last_index = self.layers.len() - 1;
-        let outputs: Vec<Array<f32>> = self.param_manager.get_outputs(last_index);
+        source.shutdown();
+        history
+    }
 
-        outputs
+    fn forward(&self, inputs: &Array<f32>) -> Vec<Array<f32>> {
+        self.unroll(inputs, 0..self.layers.len(), true)
     }
 
     fn backward(
@@ -156,6 +783,7 @@ impl Model for Sequential {
     ) -> Vec<f32> {
         self.optimizer.setup(self.param_manager.get_all_dims());
         let mut loss_vec = Vec::with_capacity(predictions.len());
+        self.activity_loss = 0.0;
 
         for (pred, ind) in multizip((predictions.iter().rev(), (0..predictions.len()).rev())) {
             let tar = af::slice(&targets, ind as i64);
@@ -176,16 +804,255 @@ impl Model for Sequential {
             };
 
             for i in (0..last_index).rev() {
+                // inject the activity penalty gradient w.r.t. this layer's outputs
+                if let Some(regularizer) = &self.activity_regularizers[i] {
+                    let outputs = self.param_manager.get_outputs(i)[ind].clone();
+                    self.activity_loss += regularizer.penalty(&outputs);
+                    delta = af::add(&delta, &regularizer.derivative(&outputs), false);
+                }
                 delta = self.layers[i].backward(self.param_manager.get_params(i), &delta);
             }
         }
+        self.activity_loss /= predictions.len().max(1) as f32;
+
+        // parameter penalties contribute once per backward pass
+        let penalty: f32 = self
+            .regularizers
+            .iter()
+            .map(|r| r.apply(&self.param_manager))
+            .sum();
+        for loss in loss_vec.iter_mut() {
+            *loss += penalty;
+        }
 
         loss_vec
     }
 
+    fn evaluate<T>(&self, source: &T, batch_size: u64) -> Result<HashMap<String, f32>, HALError>
+    where
+        T: DataSouce,
+    {
+        if batch_size == 0 {
+            return Err(HALError::UNKNOWN);
+        }
+        Ok(self
+            .mean_logs(source.num_test_samples(), batch_size, |n| {
+                Some(source.get_test_iter(n))
+            })
+            .unwrap_or_default())
+    }
+
+    fn predict(&self, inputs: &Array<f32>, batch_size: u64) -> Result<Array<f32>, HALError> {
+        if batch_size == 0 {
+            return Err(HALError::UNKNOWN);
+        }
+        let num_samples = inputs.dims()[0];
+        let mut outputs: Option<Array<f32>> = None;
+        for start in (0..num_samples).step_by(batch_size as usize) {
+            let end = (start + batch_size).min(num_samples);
+            let indices = (start as u32..end as u32).collect::<Vec<u32>>();
+            let chunk = join_slices(&self.predict_slices(&data::gather_rows(inputs, &indices)));
+            outputs = Some(match outputs {
+                Some(previous) => af::join(0, &previous, &chunk),
+                None => chunk,
+            });
+        }
+        outputs.ok_or(HALError::UNKNOWN)
+    }
+
+    fn predict_batches<T>(&self, source: &T, batch_size: u64) -> Result<Array<f32>, HALError>
+    where
+        T: DataSouce,
+    {
+        if batch_size == 0 {
+            return Err(HALError::UNKNOWN);
+        }
+        let num_samples = source.num_test_samples();
+        let mut outputs: Option<Array<f32>> = None;
+        let mut count = 0u64;
+        while count < num_samples {
+            let batch = truncate_batch(source.get_test_iter(batch_size), num_samples - count);
+            count += batch.input.dims()[0];
+            let chunk = join_slices(&self.predict_slices(&batch.input));
+            outputs = Some(match outputs {
+                Some(previous) => af::join(0, &previous, &chunk),
+                None => chunk,
+            });
+        }
+        outputs.ok_or(HALError::UNKNOWN)
+    }
+
     fn info(&self) {
         println!("model info!!");
         dbg!(&self.param_manager.layer_storage);
         // println!("num_layers: {}", self.layers.len());
     }
 }
+
+/// Weighted sum & total weight of every log, a metric may be missing from some batches
+type LogSums = HashMap<String, (f32, f32)>;
+
+fn accumulate_logs(sums: &mut LogSums, logs: &HashMap<String, f32>, weight: f32) {
+    for (name, value) in logs {
+        let (sum, total) = sums.entry(name.clone()).or_insert((0.0, 0.0));
+        *sum += value * weight;
+        *total += weight;
+    }
+}
+
+/// Averages every log over the batches that actually computed it
+fn average_logs(sums: LogSums) -> HashMap<String, f32> {
+    sums.into_iter()
+        .map(|(name, (sum, total))| (name, sum / total))
+        .collect()
+}
+
+/// Stacks per time slice outputs back into a [batch, feature, time] Array
+fn join_slices(slices: &[Array<f32>]) -> Array<f32> {
+    slices[1..].iter().fold(slices[0].clone(), |joined, slice| {
+        af::join(2, &joined, slice)
+    })
+}
+
+/// Keeps at most `num_samples` samples of a batch, ie: drops the wrapped around
+/// samples of sources whose iterators always serve full batches
+fn truncate_batch(batch: Data, num_samples: u64) -> Data {
+    if batch.input.dims()[0] <= num_samples {
+        return batch;
+    }
+    let indices = (0..num_samples as u32).collect::<Vec<u32>>();
+    Data {
+        input: data::gather_rows(&batch.input, &indices),
+        target: data::gather_rows(&batch.target, &indices),
+        labels: batch
+            .labels
+            .as_ref()
+            .map(|labels| data::gather_rows(labels, &indices)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::InMemorySource;
+    use crate::test_utils::{dense_model, dense_params, sgd_model};
+    use af::Dim4;
+
+    #[test]
+    fn evaluate_matches_predict() {
+        let model = dense_model(&[(4, 4)], "tanh");
+
+        let input = utils::vec_to_array(
+            (0..20).map(|v| v as f32 / 20.0).collect(),
+            Dim4::new(&[5, 4, 1, 1]),
+        );
+        let source = InMemorySource::autoencoder(input.copy(), 2, false, false, 0);
+        let predictions = model.predict(&input, 2).unwrap();
+        assert_eq!(predictions.dims(), input.dims());
+        assert_eq!(
+            utils::array_to_vec(&predictions),
+            utils::array_to_vec(&model.predict_batches(&source, 2).unwrap())
+        );
+
+        let expected = loss::mse(&predictions, &input);
+        let evaluated = model.evaluate(&source, 2).unwrap()["loss"];
+        assert!((evaluated - expected).abs() < 1e-5);
+
+        // training runs the same unrolled pass as inference
+        let trained = join_slices(&model.forward(&input));
+        assert_eq!(
+            utils::array_to_vec(&trained),
+            utils::array_to_vec(&predictions)
+        );
+    }
+
+    #[test]
+    fn inference_rejects_zero_batches_and_empty_sources() {
+        let model = dense_model(&[(4, 4)], "tanh");
+        let input = utils::constant(Dim4::new(&[3, 4, 1, 1]), 0.5f32);
+        assert!(model.predict(&input, 0).is_err());
+
+        let source = crate::data::SinSource::new(4, 2, af::DType::F32, 8);
+        assert!(model.evaluate(&source, 0).is_err());
+        // the sin wave has no held-out samples
+        assert!(model.predict_batches(&source, 2).is_err());
+    }
+
+    #[test]
+    fn log_means_skip_batches_without_the_metric() {
+        let mut sums = LogSums::new();
+        let first = [("loss", 1.0), ("mae", 2.0)];
+        let second = [("loss", 3.0)];
+        for logs in [&first[..], &second[..]].iter() {
+            let logs = logs
+                .iter()
+                .map(|(k, v)| (k.to_string(), *v))
+                .collect::<HashMap<String, f32>>();
+            accumulate_logs(&mut sums, &logs, 1.0);
+        }
+        let means = average_logs(sums);
+        assert_eq!(means["loss"], 2.0);
+        assert_eq!(means["mae"], 2.0);
+    }
+
+    #[test]
+    fn undefined_metrics_are_left_out() {
+        let mut model = dense_model(&[(3, 3)], "tanh");
+        assert!(model.add_metric("unknown").is_err());
+        model.add_metric("mae").unwrap();
+        // 3 features are no square image
+        model.add_metric("ssim").unwrap();
+
+        let input = utils::constant(Dim4::new(&[2, 3, 1, 1]), 0.5f32);
+        let source = InMemorySource::autoencoder(input, 2, false, false, 0);
+        let logs = model.evaluate(&source, 2).unwrap();
+        assert!(logs.contains_key("mae"));
+        assert!(!logs.contains_key("ssim"));
+    }
+
+    #[test]
+    #[should_panic(expected = "contractive_weight requires a sigmoid or tanh activation")]
+    fn contractive_penalty_rejects_relu() {
+        let mut model = sgd_model();
+        let mut params = dense_params(4, 2, "relu");
+        params.insert("contractive_weight", "0.1".to_string());
+        model.add("dense", params);
+    }
+
+    /// A sigmoid 4 -> 2 "encoder" and a decoder tied to it
+    fn tied_autoencoder(extra: Option<(&'static str, &str)>) -> Sequential {
+        let mut model = sgd_model();
+        let mut encoder = dense_params(4, 2, "sigmoid");
+        encoder.insert("name", "encoder".to_string());
+        model.add("dense", encoder);
+
+        let mut decoder = dense_params(2, 4, "sigmoid");
+        decoder.insert("tied_to", "encoder".to_string());
+        // only read when a w_regularizer is set
+        decoder.insert("w_l2", "0.01".to_string());
+        if let Some((key, value)) = extra {
+            decoder.insert(key, value.to_string());
+        }
+        model.add("dense", decoder);
+        model
+    }
+
+    #[test]
+    fn tied_to_resolves_layer_names() {
+        let model = tied_autoencoder(None);
+        let input = utils::constant(Dim4::new(&[3, 4, 1, 1]), 0.5f32);
+        assert_eq!(model.forward(&input)[0].dims(), input.dims());
+    }
+
+    #[test]
+    #[should_panic(expected = "w_constraint is not supported on a tied layer")]
+    fn tied_layers_reject_weight_constraints() {
+        tied_autoencoder(Some(("w_constraint", "unit_norm")));
+    }
+
+    #[test]
+    #[should_panic(expected = "w_regularizer is not supported on a tied layer")]
+    fn tied_layers_reject_weight_regularizers() {
+        tied_autoencoder(Some(("w_regularizer", "l2")));
+    }
+}
@@ -17,6 +17,12 @@ pub trait Optimizer {
     fn setup(&mut self, dims: Vec<Dim4>);
 
     fn update(&mut self, parameter_manager: &mut ParamManager, batch_size: u64);
+
+    /// Returns the base learning rate
+    fn learning_rate(&self) -> f32;
+
+    /// Sets the base learning rate (eg: from a learning rate schedule)
+    fn set_learning_rate(&mut self, learning_rate: f32);
 }
 
 pub fn get_optimizer_with_defaults(name: &str) -> Result<Box<dyn Optimizer>, HALError> {
@@ -26,6 +32,35 @@ pub fn get_optimizer_with_defaults(name: &str) -> Result<Box<dyn Optimizer>, HAL
     }
 }
 
+/// Helper to provide an optimizer from a string and its params
+///
+/// Missing params take the defaults of the optimizer, unknown or non numeric
+/// params are rejected. "momentum" is accepted for SGD's "momemtum".
+pub fn get_optimizer(
+    name: &str,
+    params: &HashMap<&str, &str>,
+) -> Result<Box<dyn Optimizer>, HALError> {
+    match name.to_lowercase().as_str() {
+        "sgd" => {
+            let mut sgd_params: HashMap<&str, &str> =
+                [("learning_rate", "1e-3"), ("momemtum", "0"), ("decay", "0")]
+                    .iter()
+                    .cloned()
+                    .collect();
+            for (key, value) in params {
+                let key = if *key == "momentum" { "momemtum" } else { *key };
+                if !sgd_params.contains_key(key) {
+                    return Err(HALError::UNKNOWN);
+                }
+                value.parse::<f32>().map_err(|_| HALError::PARSE_ERROR)?;
+                sgd_params.insert(key, value);
+            }
+            Ok(Box::new(SGD::new(&sgd_params)))
+        }
+        _ => Err(HALError::UNKNOWN),
+    }
+}
+
 // pub fn clip_grad(input: &Array<f32>, rescale: f32) -> Array<f32> {
 //     let norm = af::norm(input, NormType::VECTOR_2, 0f64, 0f64) as f32;
 //     let scale = rescale / norm.max(rescale);
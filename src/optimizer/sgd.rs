@@ -77,4 +77,12 @@ impl Optimizer for SGD {
 
         parameter_manager.zero_all_deltas();
     }
+
+    fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
 }
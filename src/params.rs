@@ -56,6 +56,20 @@ pub struct Params {
     pub inputs: Vec<Array<f32>>,
     pub outputs: Vec<Array<f32>>,
     pub current_unroll: usize,
+    // layer whose (transposed) weight is shared with this layer
+    pub tied_weights: Option<Arc<Mutex<Params>>>,
+}
+
+impl Params {
+    /// Returns the weight used by the layer
+    ///
+    /// Tied layers own no weight and use the transpose of their source layer's weight
+    pub fn effective_weight(&self) -> Array<f32> {
+        match &self.tied_weights {
+            Some(source) => af::transpose(&source.lock().unwrap().weights[0], false),
+            None => self.weights[0].clone(),
+        }
+    }
 }
 
 pub struct ParamManager {
@@ -109,6 +123,7 @@ impl ParamManager {
             inputs: Vec::new(),
             outputs: Vec::new(),
             current_unroll: 0,
+            tied_weights: None,
         })));
     }
 
@@ -251,6 +266,14 @@ pub trait DenseGenerator {
         w_init: &str,
         b_init: &str,
     );
+
+    fn add_tied_dense(
+        &mut self,
+        source_index: usize,
+        output_size: usize,
+        activation: &str,
+        b_init: &str,
+    );
 }
 
 impl DenseGenerator for ParamManager {
@@ -269,6 +292,26 @@ impl DenseGenerator for ParamManager {
             vec![activation],
         )
     }
+
+    fn add_tied_dense(
+        &mut self,
+        source_index: usize,
+        output_size: usize,
+        activation: &str,
+        b_init: &str,
+    ) {
+        // only the bias is owned (and optimized) by the tied layer, the gradient
+        // of the weight is accumulated into the source layer's deltas
+        let source = self.get_params(source_index);
+        self.add(
+            "dense",
+            vec![],
+            vec![(b_init, (output_size, 1))],
+            vec![activation],
+        );
+        let layer = self.layer_storage.last().unwrap();
+        layer.lock().unwrap().tied_weights = Some(source);
+    }
 }
 
 #[cfg(test)]
@@ -286,4 +329,18 @@ mod tests {
         );
         dbg!(pm.get_outputs(0));
     }
+
+    #[test]
+    fn tied_dense_shares_weights() {
+        let mut pm = ParamManager::default();
+        pm.add_dense(4, 2, "tanh", "normal", "zeros");
+        pm.add_tied_dense(0, 4, "tanh", "zeros");
+
+        // the shared weight is only registered (and updated) once
+        assert_eq!(pm.num_weights(1), 0);
+        assert_eq!(pm.get_all_arrays().len(), 3);
+
+        let tied = pm.get_params(1).lock().unwrap().effective_weight();
+        assert_eq!(tied.dims(), Dim4::new(&[2, 4, 1, 1]));
+    }
 }
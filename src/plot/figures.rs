@@ -0,0 +1,335 @@
+use af::Array;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use crate::error::HALError;
+use crate::model::History;
+use crate::utils;
+
+const FONT: (&str, u32) = ("sans-serif", 16);
+
+/// Draws a figure into a PNG or SVG file, picked by the extension of the path
+///
+/// `$draw` is called with the root drawing area followed by `$args`, the file
+/// is only written when the extension is known.
+macro_rules! render {
+    ($path:expr, $width:expr, $height:expr, $draw:ident($($args:expr),*)) => {{
+        let lower = $path.to_lowercase();
+        if lower.ends_with(".svg") {
+            let root = SVGBackend::new($path, ($width, $height)).into_drawing_area();
+            $draw(&root, $($args),*)?;
+            root.present().map_err(|_| HALError::IO_ERROR)
+        } else if lower.ends_with(".png") {
+            let root = BitMapBackend::new($path, ($width, $height)).into_drawing_area();
+            $draw(&root, $($args),*)?;
+            root.present().map_err(|_| HALError::IO_ERROR)
+        } else {
+            Err(HALError::UNKNOWN)
+        }
+    }};
+}
+
+/// Drawing errors of the backends carry no more information than a failed write
+fn drawing_error<E>(_: E) -> HALError {
+    HALError::IO_ERROR
+}
+
+fn color(index: usize) -> RGBAColor {
+    Palette99::pick(index).to_rgba()
+}
+
+/// Widens empty ranges so constant data stays drawable
+fn padded(range: (f32, f32)) -> (f32, f32) {
+    if range.1 - range.0 > f32::EPSILON {
+        range
+    } else {
+        (range.0 - 0.5, range.1 + 0.5)
+    }
+}
+
+fn min_max<'a, I: Iterator<Item = &'a f32>>(values: I) -> (f32, f32) {
+    values
+        .filter(|v| v.is_finite())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+            (min.min(*v), max.max(*v))
+        })
+}
+
+/// Lines of values over their index with a legend, on a cartesian chart
+fn draw_lines<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    lines: &[(&str, &[f32])],
+    title: &str,
+    y_range: (f32, f32),
+) -> Result<(), HALError> {
+    let length = lines.iter().map(|(_, v)| v.len()).max().unwrap_or(0);
+    let (y_min, y_max) = padded(y_range);
+    let mut chart = ChartBuilder::on(area)
+        .caption(title, FONT)
+        .margin(8)
+        .x_label_area_size(24)
+        .y_label_area_size(48)
+        .build_cartesian_2d(0f32..(length.max(2) - 1) as f32, y_min..y_max)
+        .map_err(drawing_error)?;
+    chart.configure_mesh().draw().map_err(drawing_error)?;
+
+    for (i, (name, values)) in lines.iter().enumerate() {
+        let points = values
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.is_finite())
+            .map(|(x, y)| (x as f32, *y))
+            .collect::<Vec<(f32, f32)>>();
+        // a single point has no line segment to show it
+        let point_size = if points.len() == 1 { 2 } else { 0 };
+        let style = color(i).stroke_width(2);
+        chart
+            .draw_series(LineSeries::new(points, style).point_size(point_size))
+            .map_err(drawing_error)?
+            .label(*name)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 16, y)], style));
+    }
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .background_style(&WHITE.mix(0.8))
+        .border_style(&BLACK)
+        .draw()
+        .map_err(drawing_error)
+}
+
+fn draw_series<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    series: &[(&str, &[f32])],
+    title: &str,
+    y_range: (f32, f32),
+) -> Result<(), HALError> {
+    root.fill(&WHITE).map_err(drawing_error)?;
+    draw_lines(root, series, title, y_range)
+}
+
+/// Plots named series against their index (eg: losses per epoch) to a PNG or SVG file
+pub fn plot_series(
+    series: &[(&str, &[f32])],
+    title: &str,
+    path: &str,
+    width: u32,
+    height: u32,
+) -> Result<(), HALError> {
+    let y_range = min_max(series.iter().flat_map(|(_, v)| v.iter()));
+    if !y_range.0.is_finite() {
+        return Err(HALError::UNKNOWN);
+    }
+    render!(path, width, height, draw_series(series, title, y_range))
+}
+
+/// Plots the train (and validation) loss per epoch of a training run
+pub fn plot_history(
+    history: &History,
+    path: &str,
+    width: u32,
+    height: u32,
+) -> Result<(), HALError> {
+    let mut series = vec![("loss", history.epoch_losses.as_slice())];
+    if !history.validation_losses.is_empty() {
+        series.push(("val_loss", history.validation_losses.as_slice()));
+    }
+    plot_series(&series, "Loss vs. Epochs", path, width, height)
+}
+
+fn draw_reconstructions<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    samples: &[(Vec<f32>, Vec<f32>)],
+    y_range: (f32, f32),
+) -> Result<(), HALError> {
+    root.fill(&WHITE).map_err(drawing_error)?;
+    let columns = (samples.len() as f32).sqrt().ceil() as usize;
+    let rows = (samples.len() + columns - 1) / columns;
+    let panels = root.split_evenly((rows, columns));
+    for (i, (input, output)) in samples.iter().enumerate() {
+        let lines = [("input", &input[..]), ("reconstruction", &output[..])];
+        draw_lines(&panels[i], &lines, &format!("sample {}", i), y_range)?;
+    }
+    Ok(())
+}
+
+/// Plots inputs against their reconstructions, one panel per sample
+///
+/// Both Arrays are [samples, features, ...], the first `num_samples` samples
+/// are drawn as curves over the (flattened) features.
+pub fn plot_reconstructions(
+    inputs: &Array<f32>,
+    outputs: &Array<f32>,
+    num_samples: usize,
+    path: &str,
+    width: u32,
+    height: u32,
+) -> Result<(), HALError> {
+    let total = inputs.dims()[0] as usize;
+    if total == 0 || outputs.dims()[0] as usize != total {
+        return Err(HALError::UNKNOWN);
+    }
+    let (x, y) = (utils::array_to_vec(inputs), utils::array_to_vec(outputs));
+    let num_features = x.len() / total;
+    let sample = |values: &Vec<f32>, i: usize| {
+        (0..num_features)
+            .map(|j| values[i + total * j])
+            .collect::<Vec<f32>>()
+    };
+    let samples = (0..num_samples.min(total).max(1))
+        .map(|i| (sample(&x, i), sample(&y, i)))
+        .collect::<Vec<(Vec<f32>, Vec<f32>)>>();
+
+    let y_range = min_max(x.iter().chain(y.iter()));
+    if !y_range.0.is_finite() {
+        return Err(HALError::UNKNOWN);
+    }
+    render!(path, width, height, draw_reconstructions(&samples, y_range))
+}
+
+fn draw_latent<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    points: &[(f32, f32, usize)],
+    title: &str,
+) -> Result<(), HALError> {
+    root.fill(&WHITE).map_err(drawing_error)?;
+    let (x_min, x_max) = padded(min_max(points.iter().map(|(x, _, _)| x)));
+    let (y_min, y_max) = padded(min_max(points.iter().map(|(_, y, _)| y)));
+    let mut chart = ChartBuilder::on(root)
+        .caption(title, FONT)
+        .margin(8)
+        .x_label_area_size(24)
+        .y_label_area_size(48)
+        .build_cartesian_2d(x_min..x_max, y_min..y_max)
+        .map_err(drawing_error)?;
+    chart.configure_mesh().draw().map_err(drawing_error)?;
+    chart
+        .draw_series(
+            points
+                .iter()
+                .map(|(x, y, class)| Circle::new((*x, *y), 3, color(*class).filled())),
+        )
+        .map_err(drawing_error)?;
+    Ok(())
+}
+
+/// Scatter plot of the first two latent dimensions, colored by label when given
+pub fn plot_latent(
+    codes: &Array<f32>,
+    labels: Option<&Array<f32>>,
+    title: &str,
+    path: &str,
+    width: u32,
+    height: u32,
+) -> Result<(), HALError> {
+    let num_samples = codes.dims()[0] as usize;
+    let values = utils::array_to_vec(codes);
+    if num_samples == 0 || values.len() < 2 * num_samples {
+        return Err(HALError::UNKNOWN);
+    }
+    let classes = labels.map(|labels| utils::array_to_vec(labels));
+    let points = (0..num_samples)
+        .map(|i| {
+            let class = classes
+                .as_ref()
+                .map_or(0, |classes| classes[i].max(0.0).round() as usize);
+            (values[i], values[num_samples + i], class)
+        })
+        .filter(|(x, y, _)| x.is_finite() && y.is_finite())
+        .collect::<Vec<(f32, f32, usize)>>();
+    if points.is_empty() {
+        return Err(HALError::UNKNOWN);
+    }
+    render!(path, width, height, draw_latent(&points, title))
+}
+
+fn draw_histogram<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    counts: &[u64],
+    range: (f32, f32),
+    title: &str,
+) -> Result<(), HALError> {
+    root.fill(&WHITE).map_err(drawing_error)?;
+    let highest = counts.iter().max().map_or(1, |c| (*c).max(1)) as f32;
+    let mut chart = ChartBuilder::on(root)
+        .caption(title, FONT)
+        .margin(8)
+        .x_label_area_size(24)
+        .y_label_area_size(48)
+        .build_cartesian_2d(range.0..range.1, 0f32..highest)
+        .map_err(drawing_error)?;
+    chart.configure_mesh().draw().map_err(drawing_error)?;
+
+    let bin_width = (range.1 - range.0) / counts.len() as f32;
+    chart
+        .draw_series(counts.iter().enumerate().map(|(i, count)| {
+            let x0 = range.0 + i as f32 * bin_width;
+            Rectangle::new(
+                [(x0, 0.0), (x0 + bin_width, *count as f32)],
+                color(0).filled(),
+            )
+        }))
+        .map_err(drawing_error)?;
+    Ok(())
+}
+
+/// Histogram of all the values of an Array (eg: a weight matrix)
+pub fn plot_histogram(
+    values: &Array<f32>,
+    num_bins: usize,
+    title: &str,
+    path: &str,
+    width: u32,
+    height: u32,
+) -> Result<(), HALError> {
+    let values = utils::array_to_vec(values);
+    let (min, max) = padded(min_max(values.iter()));
+    if num_bins == 0 || !min.is_finite() {
+        return Err(HALError::UNKNOWN);
+    }
+
+    let mut counts = vec![0u64; num_bins];
+    for v in values.iter().filter(|v| v.is_finite()) {
+        let bin = (((v - min) / (max - min)) * num_bins as f32) as usize;
+        counts[bin.min(num_bins - 1)] += 1;
+    }
+    render!(
+        path,
+        width,
+        height,
+        draw_histogram(&counts, (min, max), title)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn series_to_svg_and_png() {
+        let losses = [1.0, 0.5, 0.25, 0.2];
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["losses.svg", "losses.png"].iter() {
+            let path = dir.path().join(name);
+            plot_series(
+                &[("loss", &losses)],
+                "loss",
+                path.to_str().unwrap(),
+                320,
+                240,
+            )
+            .unwrap();
+            assert!(std::fs::metadata(&path).unwrap().len() > 0);
+        }
+        let path = dir.path().join("losses.txt");
+        assert!(plot_series(
+            &[("loss", &losses)],
+            "loss",
+            path.to_str().unwrap(),
+            320,
+            240
+        )
+        .is_err());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,294 @@
+use std::fs;
+
+use af::{Array, Dim4};
+
+use crate::error::HALError;
+use crate::model::Sequential;
+use crate::plot::encode_png;
+use crate::utils;
+
+/// Layout of an image grid
+///
+/// Images are [samples, height * width * channels] with the pixels of a sample
+/// stored row major (as read by `IdxSource` with `flatten`), or unflattened
+/// [samples, height, width, channels] Arrays. Every pixel is drawn as a
+/// `scale` x `scale` block and cells are separated by `padding` pixels.
+#[derive(Clone, Debug)]
+pub struct GridParams {
+    pub image_height: u32,
+    pub image_width: u32,
+    pub channels: u32,
+    pub rows: u32,
+    pub cols: u32,
+    pub scale: u32,
+    pub padding: u32,
+}
+
+impl GridParams {
+    /// 2 rows of 8 single channel images scaled 2x
+    pub fn new(image_height: u32, image_width: u32) -> GridParams {
+        GridParams {
+            image_height,
+            image_width,
+            channels: 1,
+            rows: 2,
+            cols: 8,
+            scale: 2,
+            padding: 2,
+        }
+    }
+}
+
+/// Host copy of a batch of images with the value range used to scale them
+struct Images {
+    values: Vec<f32>,
+    num_samples: usize,
+    unflattened: bool,
+}
+
+impl Images {
+    fn new(images: &Array<f32>, params: &GridParams) -> Result<Images, HALError> {
+        let dims = images.dims();
+        let pixels = (params.image_height * params.image_width * params.channels) as u64;
+        let unflattened = dims[1] == params.image_height as u64
+            && dims[2] == params.image_width as u64
+            && dims[3] == params.channels as u64;
+        if !unflattened && (dims[1] != pixels || dims[2] != 1) {
+            return Err(HALError::UNKNOWN);
+        }
+        Ok(Images {
+            values: utils::array_to_vec(images),
+            num_samples: dims[0] as usize,
+            unflattened,
+        })
+    }
+
+    fn pixel(&self, params: &GridParams, sample: usize, r: usize, c: usize, ch: usize) -> f32 {
+        let (h, w) = (params.image_height as usize, params.image_width as usize);
+        let feature = if self.unflattened {
+            r + h * (c + w * ch)
+        } else {
+            (r * w + c) * params.channels as usize + ch
+        };
+        self.values[sample + self.num_samples * feature]
+    }
+}
+
+/// An RGB image the grid cells are blitted into
+struct Sheet {
+    rgb: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+impl Sheet {
+    fn new(params: &GridParams, rows: u32, cols: u32) -> Sheet {
+        let (cell_w, cell_h) = cell_size(params);
+        let width = cols * cell_w + params.padding;
+        let height = rows * cell_h + params.padding;
+        Sheet {
+            rgb: vec![255; (width * height * 3) as usize],
+            width,
+            height,
+        }
+    }
+
+    /// Draws a sample into the cell at (row, col), values are mapped from `range` to [0, 255]
+    fn draw(
+        &mut self,
+        params: &GridParams,
+        images: &Images,
+        sample: usize,
+        cell: (u32, u32),
+        range: (f32, f32),
+    ) {
+        let (cell_w, cell_h) = cell_size(params);
+        let left = cell.1 * cell_w + params.padding;
+        let top = cell.0 * cell_h + params.padding;
+        let span = (range.1 - range.0).max(1e-12);
+        for r in 0..params.image_height {
+            for c in 0..params.image_width {
+                let mut color = [0u8; 3];
+                for (k, value) in color.iter_mut().enumerate() {
+                    // single channel images are drawn in grayscale
+                    let ch = if params.channels >= 3 { k } else { 0 };
+                    let v = images.pixel(params, sample, r as usize, c as usize, ch);
+                    *value = (((v - range.0) / span).max(0.0).min(1.0) * 255.0).round() as u8;
+                }
+                for dy in 0..params.scale {
+                    for dx in 0..params.scale {
+                        let x = left + c * params.scale + dx;
+                        let y = top + r * params.scale + dy;
+                        let offset = ((y * self.width + x) * 3) as usize;
+                        self.rgb[offset..offset + 3].copy_from_slice(&color);
+                    }
+                }
+            }
+        }
+    }
+
+    fn save(&self, path: &str) -> Result<(), HALError> {
+        let png = encode_png(&self.rgb, self.width, self.height)?;
+        fs::write(path, png).map_err(|_| HALError::IO_ERROR)
+    }
+}
+
+fn cell_size(params: &GridParams) -> (u32, u32) {
+    (
+        params.image_width * params.scale + params.padding,
+        params.image_height * params.scale + params.padding,
+    )
+}
+
+/// Common value range of all the images, so they are scaled consistently
+fn value_range(images: &[&Images]) -> (f32, f32) {
+    images
+        .iter()
+        .flat_map(|images| images.values.iter())
+        .filter(|v| v.is_finite())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+            (min.min(*v), max.max(*v))
+        })
+}
+
+/// Writes a PNG of inputs with their reconstructions right below them
+///
+/// Each of the `rows` bands holds `cols` originals above their reconstructions,
+/// so up to rows * cols samples are shown (fewer when the batch is smaller).
+pub fn save_reconstruction_grid(
+    inputs: &Array<f32>,
+    outputs: &Array<f32>,
+    params: &GridParams,
+    path: &str,
+) -> Result<(), HALError> {
+    let originals = Images::new(inputs, params)?;
+    let reconstructions = Images::new(outputs, params)?;
+    if originals.num_samples != reconstructions.num_samples || params.cols == 0 {
+        return Err(HALError::UNKNOWN);
+    }
+    let num_samples = originals
+        .num_samples
+        .min((params.rows * params.cols) as usize);
+    let bands = (num_samples as u32 + params.cols - 1) / params.cols;
+    let range = value_range(&[&originals, &reconstructions]);
+
+    let mut sheet = Sheet::new(params, 2 * bands, params.cols);
+    for i in 0..num_samples {
+        let (band, col) = (i as u32 / params.cols, i as u32 % params.cols);
+        sheet.draw(params, &originals, i, (2 * band, col), range);
+        sheet.draw(params, &reconstructions, i, (2 * band + 1, col), range);
+    }
+    sheet.save(path)
+}
+
+/// Writes a PNG of decoded sweeps along every latent dimension
+///
+/// `code` is the [1, latent] code the sweep starts from (eg: the encoding of
+/// a sample or zeros), row j decodes `params.cols` codes where dimension j is
+/// offset linearly from -`extent` to +`extent`. Only the first `params.rows`
+/// latent dimensions are swept. The decoder is made of the layers from
+/// `first_decoder_layer` to the output of the model.
+pub fn save_latent_traversal(
+    model: &Sequential,
+    first_decoder_layer: usize,
+    code: &Array<f32>,
+    extent: f32,
+    params: &GridParams,
+    path: &str,
+) -> Result<(), HALError> {
+    let latent_dims = code.dims()[1] as usize;
+    let swept_dims = latent_dims.min(params.rows as usize);
+    let steps = params.cols as usize;
+    if code.dims()[0] != 1 || steps == 0 || swept_dims == 0 {
+        return Err(HALError::UNKNOWN);
+    }
+    let base = utils::array_to_vec(code);
+
+    // one sample per (dimension, step), samples are rows of the [n, latent] codes
+    let num_codes = swept_dims * steps;
+    let mut codes = vec![0.0f32; num_codes * latent_dims];
+    for dim in 0..swept_dims {
+        for step in 0..steps {
+            let offset = match steps {
+                1 => 0.0,
+                _ => -extent + 2.0 * extent * step as f32 / (steps - 1) as f32,
+            };
+            let sample = dim * steps + step;
+            for j in 0..latent_dims {
+                let shift = if j == dim { offset } else { 0.0 };
+                codes[sample + num_codes * j] = base[j] + shift;
+            }
+        }
+    }
+    let codes = utils::vec_to_array(
+        codes,
+        Dim4::new(&[num_codes as u64, latent_dims as u64, 1, 1]),
+    );
+    let decoded = Images::new(&model.decode(&codes, first_decoder_layer), params)?;
+    let range = value_range(&[&decoded]);
+
+    let mut sheet = Sheet::new(params, swept_dims as u32, params.cols);
+    for sample in 0..num_codes {
+        let cell = ((sample / steps) as u32, (sample % steps) as u32);
+        sheet.draw(params, &decoded, sample, cell, range);
+    }
+    sheet.save(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::dense_model;
+
+    /// Width & height from the IHDR chunk of a PNG file
+    fn png_size(path: &std::path::Path) -> (u32, u32) {
+        let png = fs::read(path).unwrap();
+        let size = |at: usize| u32::from_be_bytes([png[at], png[at + 1], png[at + 2], png[at + 3]]);
+        (size(16), size(20))
+    }
+
+    #[test]
+    fn reconstruction_grid_size() {
+        let values = (0..3 * 4).map(|v| v as f32).collect::<Vec<f32>>();
+        let images = utils::vec_to_array(values, Dim4::new(&[3, 4, 1, 1]));
+        let params = GridParams {
+            cols: 2,
+            ..GridParams::new(2, 2)
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("grid.png");
+        save_reconstruction_grid(&images, &images, &params, path.to_str().unwrap()).unwrap();
+
+        // 2 bands of (original, reconstruction) rows of 2 cells
+        let cell = 2 * 2 + 2;
+        assert_eq!(png_size(&path), (2 * cell + 2, 4 * cell + 2));
+
+        let wrong = GridParams::new(3, 3);
+        assert!(save_reconstruction_grid(
+            &images,
+            &images,
+            &wrong,
+            dir.path().join("unused.png").to_str().unwrap()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn latent_traversal_size() {
+        // 3 latent dimensions decoded into 2x2 images
+        let model = dense_model(&[(4, 3), (3, 4)], "sigmoid");
+        let code = af::constant(0.0f32, Dim4::new(&[1, 3, 1, 1]));
+        let params = GridParams {
+            rows: 2,
+            cols: 5,
+            ..GridParams::new(2, 2)
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("traversal.png");
+        save_latent_traversal(&model, 1, &code, 2.0, &params, path.to_str().unwrap()).unwrap();
+
+        // only the first `rows` of the 3 latent dimensions are swept
+        let cell = 2 * 2 + 2;
+        assert_eq!(png_size(&path), (5 * cell + 2, 2 * cell + 2));
+    }
+}
@@ -1,7 +1,39 @@
-use af::{Array, Dim4, HasAfEnum};
+mod figures;
+mod grid;
 
+pub use self::figures::{
+    plot_histogram, plot_history, plot_latent, plot_reconstructions, plot_series,
+};
+pub use self::grid::{save_latent_traversal, save_reconstruction_grid, GridParams};
+
+use crate::error::HALError;
+
+/// Encodes a row major RGB buffer as an 8 bit truecolor PNG
+pub fn encode_png(rgb: &[u8], width: u32, height: u32) -> Result<Vec<u8>, HALError> {
+    if rgb.len() != 3 * width as usize * height as usize {
+        return Err(HALError::UNKNOWN);
+    }
+
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|_| HALError::IO_ERROR)?;
+    writer
+        .write_image_data(rgb)
+        .map_err(|_| HALError::IO_ERROR)?;
+    writer.finish().map_err(|_| HALError::IO_ERROR)?;
+    Ok(bytes)
+}
+
+#[cfg(feature = "window")]
+use af::{Array, Dim4};
+
+#[cfg(feature = "window")]
 use crate::utils;
 
+/// Displays the values in an ArrayFire window until it is closed
+#[cfg(feature = "window")]
 pub fn plot_array(values: &Array<f32>, title: &str, window_x: u16, window_y: u16) {
     assert!(values.dims()[1] == 1);
 
@@ -16,6 +48,7 @@ pub fn plot_array(values: &Array<f32>, title: &str, window_x: u16, window_y: u16
     }
 }
 
+#[cfg(feature = "window")]
 pub fn plot_vec(raw_values: Vec<f32>, title: &str, window_x: u16, window_y: u16) {
     let num_rows = raw_values.len();
     let dims = Dim4::new(&[num_rows as u64, 1, 1, 1]);
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+
+use af::{self, Array, Dim4, MatProp};
+
+use crate::error::HALError;
+use crate::params::ParamManager;
+use crate::utils;
+
+/// Minimum distance of the mean activation from 0 and 1 to keep the KL finite
+const KL_EPSILON: f32 = 1e-6;
+
+/// An activity penalty attached to the outputs of a layer
+///
+/// Derivatives are returned per sample (like the loss derivatives),
+/// the optimizer takes care of dividing by the batch size.
+#[derive(Clone, Debug)]
+pub enum ActivityRegularizer {
+    /// weight * sum(|a|)
+    L1 { weight: f32 },
+    /// beta * sum_j KL(rho || mean_j(a)), requires activations in (0, 1)
+    KLSparsity { rho: f32, beta: f32 },
+}
+
+impl ActivityRegularizer {
+    /// Returns the penalty averaged over the minibatch
+    pub fn penalty(&self, activations: &Array<f32>) -> f32 {
+        match *self {
+            ActivityRegularizer::L1 { weight } => l1_activity(activations, weight),
+            ActivityRegularizer::KLSparsity { rho, beta } => kl_sparsity(activations, rho, beta),
+        }
+    }
+
+    /// Returns the gradient of the penalty w.r.t. the activations
+    pub fn derivative(&self, activations: &Array<f32>) -> Array<f32> {
+        match *self {
+            ActivityRegularizer::L1 { weight } => l1_activity_derivative(activations, weight),
+            ActivityRegularizer::KLSparsity { rho, beta } => {
+                kl_sparsity_derivative(activations, rho, beta)
+            }
+        }
+    }
+}
+
+/// Provide the l1 activity penalty: weight * mean_batch(sum(|a|))
+pub fn l1_activity(activations: &Array<f32>, weight: f32) -> f32 {
+    let batch_size = activations.dims()[0] as f32;
+    weight * af::sum_all(&af::abs(activations)).0 as f32 / batch_size
+}
+
+/// Provides the vector derivative of the l1 activity penalty: weight * sign(a)
+pub fn l1_activity_derivative(activations: &Array<f32>, weight: f32) -> Array<f32> {
+    let positive = af::gt(activations, &0.0f32, false).cast::<f32>();
+    let negative = af::lt(activations, &0.0f32, false).cast::<f32>();
+    af::mul(&weight, &af::sub(&positive, &negative, false), false)
+}
+
+/// Returns the mean activation of each unit over the minibatch, clamped to (0, 1)
+fn mean_activations(activations: &Array<f32>) -> Vec<f32> {
+    let dims = activations.dims();
+    let batch_size = dims[0] as usize;
+    let values = utils::array_to_vec(activations);
+    values
+        .chunks(batch_size)
+        .map(|unit| {
+            let mean = unit.iter().sum::<f32>() / batch_size as f32;
+            mean.max(KL_EPSILON).min(1.0 - KL_EPSILON)
+        })
+        .collect()
+}
+
+/// Provide the KL sparsity penalty toward a target mean activation rho
+/// beta * sum_j [rho * log(rho / rho_j) + (1 - rho) * log((1 - rho) / (1 - rho_j))]
+pub fn kl_sparsity(activations: &Array<f32>, rho: f32, beta: f32) -> f32 {
+    let kl = mean_activations(activations)
+        .iter()
+        .map(|rho_j| rho * (rho / rho_j).ln() + (1.0 - rho) * ((1.0 - rho) / (1.0 - rho_j)).ln())
+        .sum::<f32>();
+    beta * kl
+}
+
+/// Provides the vector derivative of the KL sparsity penalty
+/// beta * (-rho / rho_j + (1 - rho) / (1 - rho_j)), broadcast over the minibatch
+pub fn kl_sparsity_derivative(activations: &Array<f32>, rho: f32, beta: f32) -> Array<f32> {
+    let dims = activations.dims();
+    let grad = mean_activations(activations)
+        .iter()
+        .map(|rho_j| beta * (-rho / rho_j + (1.0 - rho) / (1.0 - rho_j)))
+        .collect::<Vec<f32>>();
+    let num_units = grad.len() as u64;
+    let grad = utils::vec_to_array(grad, Dim4::new(&[1, num_units, 1, 1]));
+    af::tile(&grad, Dim4::new(&[dims[0], 1, 1, 1]))
+}
+
+/// A penalty on the trainable parameters, evaluated once per backward pass
+///
+/// Implementations add the gradient of the penalty to the deltas held in the
+/// ParamManager (before the optimizer runs) and return the penalty value,
+/// which the model adds to the loss returned by `backward`.
+pub trait Regularizer {
+    fn apply(&self, param_manager: &ParamManager) -> f32;
+}
+
+/// Contractive autoencoder penalty on a dense sigmoid/tanh encoder
+///
+/// The activation is checked when the penalty is requested through
+/// `contractive_weight` in `Sequential::add`.
+///
+/// weight * mean_batch(||J||_F^2) where J = dh/dx is the Jacobian of the
+/// encoder outputs w.r.t. its inputs. For h = f(xW + b) this is
+/// sum_j f'(z_j)^2 * sum_i W_ij^2.
+pub struct ContractivePenalty {
+    pub layer_index: usize,
+    pub weight: f32,
+}
+
+impl ContractivePenalty {
+    /// Penalty and (dW, db) of a single time slice
+    fn slice_penalty(
+        activation: &str,
+        inputs: &Array<f32>,
+        outputs: &Array<f32>,
+        weights: &Array<f32>,
+    ) -> (f32, Array<f32>, Array<f32>) {
+        // f'(z) and d(f'(z)^2)/dz expressed in terms of the activated outputs
+        let one = utils::constant(outputs.dims(), 1.0f32);
+        let (fp, dg) = match activation {
+            "sigmoid" => {
+                let fp = af::mul(outputs, &af::sub(&one, outputs, false), false);
+                let fp2 = af::mul(&fp, &fp, false);
+                let one_minus_2h = af::sub(&one, &af::mul(&2.0f32, outputs, false), false);
+                let dg = af::mul(&2.0f32, &af::mul(&fp2, &one_minus_2h, false), false);
+                (fp, dg)
+            }
+            "tanh" => {
+                let fp = af::sub(&one, &af::mul(outputs, outputs, false), false);
+                let fp2 = af::mul(&fp, &fp, false);
+                let dg = af::mul(&-4.0f32, &af::mul(outputs, &fp2, false), false);
+                (fp, dg)
+            }
+            name => unreachable!("contractive penalty on a {} layer passed validation", name),
+        };
+
+        let g = af::mul(&fp, &fp, false); // [batch, output]
+        let w_norms = af::sum(&af::mul(weights, weights, false), 0); // [1, output]
+        let batch_size = outputs.dims()[0] as f32;
+        let penalty = af::sum_all(&af::mul(&g, &w_norms, true)).0 as f32 / batch_size;
+
+        // dW = 2 * W .* sum_batch(g) + x^T (dg .* |W|^2), db = sum_batch(dg .* |W|^2)
+        let dg_w = af::mul(&dg, &w_norms, true);
+        let dw = af::add(
+            &af::mul(&2.0f32, &af::mul(weights, &af::sum(&g, 0), true), false),
+            &af::matmul(inputs, &dg_w, MatProp::TRANS, MatProp::NONE),
+            false,
+        );
+        let db = af::transpose(&af::sum(&dg_w, 0), false);
+        (penalty, dw, db)
+    }
+}
+
+impl Regularizer for ContractivePenalty {
+    /// Like the loss, the penalty is averaged over the time slices while the
+    /// gradients of every slice are accumulated
+    fn apply(&self, param_manager: &ParamManager) -> f32 {
+        let params = param_manager.get_params(self.layer_index);
+        let mut ltex = params.lock().unwrap();
+        let weights = ltex.weights[0].clone();
+        let activation = ltex.activations[0].clone();
+        let num_slices = ltex.inputs.len();
+
+        let mut penalty = 0.0f32;
+        let bias_index = ltex.weights.len();
+        for t in 0..num_slices {
+            let (inputs, outputs) = (ltex.inputs[t].clone(), ltex.outputs[t].clone());
+            let (slice_penalty, dw, db) =
+                ContractivePenalty::slice_penalty(&activation, &inputs, &outputs, &weights);
+            penalty += slice_penalty;
+            ltex.deltas[0] = af::add(&ltex.deltas[0], &af::mul(&self.weight, &dw, false), false);
+            ltex.deltas[bias_index] = af::add(
+                &ltex.deltas[bias_index],
+                &af::mul(&self.weight, &db, false),
+                false,
+            );
+        }
+
+        self.weight * penalty / num_slices.max(1) as f32
+    }
+}
+
+/// An elastic net penalty on a parameter array: l1 * sum(|w|) + l2 * sum(w^2)
+///
+/// Pure l1 and l2 penalties are the special cases with the other weight at zero.
+#[derive(Clone, Debug)]
+pub struct WeightRegularizer {
+    pub l1: f32,
+    pub l2: f32,
+}
+
+impl WeightRegularizer {
+    pub fn penalty(&self, w: &Array<f32>) -> f32 {
+        let l1 = af::sum_all(&af::abs(w)).0 as f32;
+        let l2 = af::sum_all(&af::mul(w, w, false)).0 as f32;
+        self.l1 * l1 + self.l2 * l2
+    }
+
+    pub fn derivative(&self, w: &Array<f32>) -> Array<f32> {
+        af::add(
+            &l1_activity_derivative(w, self.l1),
+            &af::mul(&(2.0 * self.l2), w, false),
+            false,
+        )
+    }
+}
+
+/// Kernel and bias penalties of a single layer
+pub struct WeightPenalty {
+    pub layer_index: usize,
+    pub kernel: Option<WeightRegularizer>,
+    pub bias: Option<WeightRegularizer>,
+}
+
+impl Regularizer for WeightPenalty {
+    fn apply(&self, param_manager: &ParamManager) -> f32 {
+        let params = param_manager.get_params(self.layer_index);
+        let mut ltex = params.lock().unwrap();
+
+        // the optimizer divides the deltas by the batch size, scale the
+        // gradient so the penalty is applied with its nominal weight
+        let batch_size = ltex.inputs[0].dims()[0] as f32;
+        let num_weights = ltex.weights.len();
+        let mut penalty = 0.0f32;
+
+        if let Some(regularizer) = &self.kernel {
+            for i in 0..num_weights {
+                let w = ltex.weights[i].clone();
+                penalty += regularizer.penalty(&w);
+                let grad = af::mul(&batch_size, &regularizer.derivative(&w), false);
+                ltex.deltas[i] = af::add(&ltex.deltas[i], &grad, false);
+            }
+        }
+
+        if let Some(regularizer) = &self.bias {
+            for i in 0..ltex.biases.len() {
+                let b = ltex.biases[i].clone();
+                penalty += regularizer.penalty(&b);
+                let grad = af::mul(&batch_size, &regularizer.derivative(&b), false);
+                ltex.deltas[num_weights + i] = af::add(&ltex.deltas[num_weights + i], &grad, false);
+            }
+        }
+
+        penalty
+    }
+}
+
+/// Helper to provide a weight regularizer from a string and the layer params
+///
+/// The weights are read from `{prefix}_l1` and `{prefix}_l2`
+/// (eg: `w_l1`, `b_l2`), `elastic_net` reads both.
+pub fn get_weight_regularizer(
+    name: &str,
+    prefix: &str,
+    params: &HashMap<&str, String>,
+) -> Result<WeightRegularizer, HALError> {
+    let get = |key: &str| -> Result<f32, HALError> {
+        params
+            .get(format!("{}_{}", prefix, key).as_str())
+            .ok_or(HALError::UNKNOWN)?
+            .parse::<f32>()
+            .map_err(|_| HALError::UNKNOWN)
+    };
+    match name {
+        "l1" => Ok(WeightRegularizer {
+            l1: get("l1")?,
+            l2: 0.0,
+        }),
+        "l2" => Ok(WeightRegularizer {
+            l1: 0.0,
+            l2: get("l2")?,
+        }),
+        "elastic_net" => Ok(WeightRegularizer {
+            l1: get("l1")?,
+            l2: get("l2")?,
+        }),
+        _ => Err(HALError::UNKNOWN),
+    }
+}
+
+/// Helper to provide an activity regularizer from a string and the layer params
+///
+/// - `l1` reads `activity_weight`
+/// - `kl` reads `sparsity_target` (rho) and `sparsity_weight` (beta)
+pub fn get_activity_regularizer(
+    name: &str,
+    params: &HashMap<&str, String>,
+) -> Result<ActivityRegularizer, HALError> {
+    let get = |key: &str| -> Result<f32, HALError> {
+        params
+            .get(key)
+            .ok_or(HALError::UNKNOWN)?
+            .parse::<f32>()
+            .map_err(|_| HALError::UNKNOWN)
+    };
+    match name {
+        "l1" => Ok(ActivityRegularizer::L1 {
+            weight: get("activity_weight")?,
+        }),
+        "kl" => Ok(ActivityRegularizer::KLSparsity {
+            rho: get("sparsity_target")?,
+            beta: get("sparsity_weight")?,
+        }),
+        _ => Err(HALError::UNKNOWN),
+    }
+}
@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+
+use crate::hashmap;
+use crate::model::{Model, Sequential};
+use crate::optimizer::get_optimizer_with_defaults;
+
+/// Returns an empty mse model trained with the default sgd
+pub(crate) fn sgd_model() -> Sequential {
+    Sequential::new(get_optimizer_with_defaults("sgd").unwrap(), "mse")
+}
+
+/// Returns the params of a normal initialized dense layer
+pub(crate) fn dense_params(
+    input_size: u64,
+    output_size: u64,
+    activation: &str,
+) -> HashMap<&'static str, String> {
+    hashmap![
+        "activation" => activation.to_string()
+        , "input_size" => input_size.to_string()
+        , "output_size" => output_size.to_string()
+        , "w_init" => "normal".to_string()
+        , "b_init" => "zeros".to_string()
+    ]
+}
+
+/// Returns a model of dense layers with the given (input, output) sizes
+pub(crate) fn dense_model(sizes: &[(u64, u64)], activation: &str) -> Sequential {
+    let mut model = sgd_model();
+    for (input_size, output_size) in sizes {
+        model.add("dense", dense_params(*input_size, *output_size, activation));
+    }
+    model
+}
@@ -1,5 +1,10 @@
 use af::{Array, DType, Dim4, HasAfEnum};
 use num::Complex;
+use rand::Rng;
+use std::fs::File;
+use std::io::Write;
+
+use crate::error::HALError;
 
 #[macro_export]
 macro_rules! hashmap {
@@ -47,6 +52,79 @@ pub fn raw_to_array(raw_values: &[f32], dims: Dim4) -> Array<f32> {
     Array::new(raw_values, dims)
 }
 
+/// Copy an Array back to the host as a (column major) vector
+pub fn array_to_vec(values: &Array<f32>) -> Vec<f32> {
+    let mut raw_values = vec![0.0f32; values.elements()];
+    values.host(&mut raw_values);
+    raw_values
+}
+
+/// Write an Array to a .npy file that numpy loads with the same shape
+///
+/// Trailing singleton dims are dropped (keeping at least 2 dims) and the
+/// values are stored in fortran order, which matches the ArrayFire layout.
+pub fn write_npy(values: &Array<f32>, path: &str) -> Result<(), HALError> {
+    let dims = values.dims();
+    let mut ndims = 4;
+    while ndims > 2 && dims[ndims - 1] == 1 {
+        ndims -= 1;
+    }
+    let shape = (0..ndims)
+        .map(|i| dims[i].to_string())
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    // the header is padded with spaces so the data starts at a multiple of 64 bytes
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': True, 'shape': ({}), }}",
+        shape
+    );
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    for v in array_to_vec(values) {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    File::create(path)
+        .and_then(|mut f| f.write_all(&bytes))
+        .map_err(|_| HALError::IO_ERROR)
+}
+
+/// Draw a standard normal sample using the Box-Muller transform
+/// Writes a [samples, columns] Array as a csv table under the given header
+///
+/// Fails, without writing anything, when the header does not name every column.
+pub fn write_csv(values: &Array<f32>, header: &[String], path: &str) -> Result<(), HALError> {
+    let num_samples = values.dims()[0] as usize;
+    let host = array_to_vec(values);
+    if header.len() * num_samples != host.len() {
+        return Err(HALError::UNKNOWN);
+    }
+
+    let mut writer = csv::Writer::from_path(path).map_err(|_| HALError::IO_ERROR)?;
+    writer
+        .write_record(header)
+        .map_err(|_| HALError::IO_ERROR)?;
+    for i in 0..num_samples {
+        let row = (0..header.len())
+            .map(|j| host[i + num_samples * j].to_string())
+            .collect::<Vec<String>>();
+        writer.write_record(&row).map_err(|_| HALError::IO_ERROR)?;
+    }
+    writer.flush().map_err(|_| HALError::IO_ERROR)
+}
+
+pub fn gaussian<R: Rng>(rng: &mut R) -> f32 {
+    let u1: f32 = rng.gen_range(f32::EPSILON..1.0);
+    let u2: f32 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+}
+
 // pub fn cast<T: HasAfEnum>(input: &Array<T>, dest_type: DType) -> Array<T> {
 //     if input.get_type() == dest_type {
 //         return input.clone();
@@ -84,4 +162,16 @@ mod tests {
         );
         dbg!(hm);
     }
+
+    #[test]
+    fn npy_round_trip() {
+        let values = vec_to_array((0..6).map(|v| v as f32).collect(), Dim4::new(&[2, 3, 1, 1]));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("round_trip.npy");
+        write_npy(&values, path.to_str().unwrap()).unwrap();
+
+        let loaded = crate::data::read_npy(path.to_str().unwrap()).unwrap();
+        assert_eq!(loaded.dims(), values.dims());
+        assert_eq!(array_to_vec(&loaded), array_to_vec(&values));
+    }
 }
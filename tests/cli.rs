@@ -0,0 +1,228 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use auto_encoder_rust::model::Checkpoint;
+
+fn autoencoder(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_autoencoder"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Runs the autoencoder binary, fails the test on a non-zero exit and returns its stdout
+fn run(args: &[&str]) -> String {
+    let output = autoencoder(args);
+    assert!(
+        output.status.success(),
+        "autoencoder {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn path_str(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+/// Writes an 8 x 4 csv table and trains a 4-2-4 model on it, returns (data, model) paths
+fn train(dir: &Path) -> (PathBuf, PathBuf) {
+    let data = dir.join("data.csv");
+    let rows = (0..8)
+        .map(|i| {
+            (0..4)
+                .map(|j| ((i * 4 + j) as f32 / 32.0).to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        })
+        .collect::<Vec<String>>();
+    fs::write(&data, format!("a,b,c,d\n{}\n", rows.join("\n"))).unwrap();
+
+    let config = dir.join("experiment.toml");
+    fs::write(
+        &config,
+        format!(
+            r#"
+[model]
+loss = "mse"
+
+[[model.layers]]
+layer = "dense"
+name = "encoder"
+activation = "sigmoid"
+input_size = 4
+output_size = 2
+w_init = "uniform"
+b_init = "zeros"
+
+[[model.layers]]
+layer = "dense"
+activation = "sigmoid"
+input_size = 2
+output_size = 4
+w_init = "uniform"
+b_init = "zeros"
+
+[optimizer]
+name = "sgd"
+learning_rate = 0.1
+
+[data]
+source = "csv"
+path = "{}"
+transform = "minmax"
+
+[training]
+epochs = 2
+batch_size = 4
+seed = 1
+"#,
+            path_str(&data)
+        ),
+    )
+    .unwrap();
+
+    let runs = dir.join("runs");
+    run(&[
+        "train",
+        "--config",
+        path_str(&config),
+        "--output",
+        path_str(&runs),
+    ]);
+    let model = runs.join("model.json");
+    assert!(model.exists());
+    (data, model)
+}
+
+#[test]
+fn train_then_reconstruct() {
+    let dir = tempfile::tempdir().unwrap();
+    let (data, model) = train(dir.path());
+    // the fitted transform is persisted with the model
+    let checkpoint = fs::read_to_string(&model).unwrap();
+    assert!(!checkpoint.contains("\"input_transform\":null"));
+
+    let reconstructions = dir.path().join("reconstructions.csv");
+    run(&[
+        "reconstruct",
+        "--checkpoint",
+        path_str(&model),
+        "--input",
+        path_str(&data),
+        "--output",
+        path_str(&reconstructions),
+        "--batch-size",
+        "4",
+    ]);
+    let text = fs::read_to_string(&reconstructions).unwrap();
+    let lines = text.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 1 + 8);
+    // reconstructions are named like the input columns
+    assert_eq!(lines[0], "a,b,c,d");
+
+    let output = autoencoder(&[
+        "reconstruct",
+        "--checkpoint",
+        path_str(&model),
+        "--input",
+        path_str(&data),
+        "--output",
+        path_str(&reconstructions),
+        "--batch-size",
+        "0",
+    ]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn evaluate_with_metrics() {
+    let dir = tempfile::tempdir().unwrap();
+    let (data, model) = train(dir.path());
+
+    let logs = run(&[
+        "evaluate",
+        "--checkpoint",
+        path_str(&model),
+        "--input",
+        path_str(&data),
+        "--metrics",
+        "mae",
+        "--batch-size",
+        "3",
+    ]);
+    let names = logs
+        .lines()
+        .map(|line| line.split(": ").next().unwrap())
+        .collect::<Vec<&str>>();
+    assert_eq!(names, ["loss", "mae"]);
+    for line in logs.lines() {
+        let value = line.split(": ").nth(1).unwrap().parse::<f32>().unwrap();
+        assert!(value.is_finite());
+    }
+}
+
+#[test]
+fn encode_to_csv() {
+    let dir = tempfile::tempdir().unwrap();
+    let (data, model) = train(dir.path());
+
+    let codes = dir.path().join("codes.csv");
+    run(&[
+        "encode",
+        "--checkpoint",
+        path_str(&model),
+        "--input",
+        path_str(&data),
+        "--layer",
+        "encoder",
+        "--output",
+        path_str(&codes),
+    ]);
+    let text = fs::read_to_string(&codes).unwrap();
+    let lines = text.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 1 + 8);
+    assert_eq!(lines[0], "z0,z1");
+}
+
+#[test]
+fn score_with_and_without_threshold() {
+    let dir = tempfile::tempdir().unwrap();
+    let (data, model) = train(dir.path());
+
+    let scores = run(&[
+        "score",
+        "--checkpoint",
+        path_str(&model),
+        "--input",
+        path_str(&data),
+    ]);
+    let lines = scores.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 1 + 8);
+    assert_eq!(lines[0], "score");
+    assert!(lines[1..].iter().all(|line| line.parse::<f32>().is_ok()));
+
+    // every score is above a negative threshold
+    let mut checkpoint = Checkpoint::load(path_str(&model)).unwrap();
+    checkpoint.anomaly_threshold = Some(-1.0);
+    let thresholded = dir.path().join("thresholded.json");
+    checkpoint.save(path_str(&thresholded)).unwrap();
+
+    let flagged = dir.path().join("flagged.csv");
+    run(&[
+        "score",
+        "--checkpoint",
+        path_str(&thresholded),
+        "--input",
+        path_str(&data),
+        "--output",
+        path_str(&flagged),
+    ]);
+    let text = fs::read_to_string(&flagged).unwrap();
+    let lines = text.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 1 + 8);
+    assert_eq!(lines[0], "score,anomaly");
+    assert!(lines[1..].iter().all(|line| line.ends_with(",1")));
+}